@@ -1,6 +1,8 @@
 //! Helpers and types for creating barcodes.
 use serde::{Deserialize, Serialize};
 
+use crate::error::ValidationError;
+
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
 /// types of available 1D barcodes.
@@ -85,6 +87,201 @@ pub enum BarcodeType {
     Gs1DatabarExpanded
 }
 
+/// Compute the GS1 modulo-10 check digit for a string of digits, as used by UPC, EAN, JAN, ITF-14 and GS1 keys such as the GTIN.
+/// Returns `None` if `digits` is empty or contains anything other than ASCII digits.
+pub fn mod10_check_digit(digits: &str) -> Option<u8> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let sum: u32 = digits.bytes().rev().enumerate()
+        .map(|(idx, b)| (b - b'0') as u32 * if idx % 2 == 0 { 3 } else { 1 })
+        .sum();
+    Some(((10 - sum % 10) % 10) as u8)
+}
+
+impl BarcodeType {
+    /// The ePOS name of the barcode type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BarcodeType::UpcA => "upc_a",
+            BarcodeType::UpcE => "upc_e",
+            BarcodeType::EAN13 => "ean13",
+            BarcodeType::JAN13 => "jan13",
+            BarcodeType::EAN8 => "ean8",
+            BarcodeType::JAN8 => "jan8",
+            BarcodeType::Code39 => "code39",
+            BarcodeType::ITF => "itf",
+            BarcodeType::CodaBar => "codabar",
+            BarcodeType::Code93 => "code93",
+            BarcodeType::Code128 => "code128",
+            BarcodeType::GS1_128 => "gs1_128",
+            BarcodeType::GS1DatabarOmnidirectional => "gs1_databar_omnidirectional",
+            BarcodeType::GS1DatabarTruncated => "gs1_databar_truncated",
+            BarcodeType::GS1DatabarLimited => "gs1_databar_limited",
+            BarcodeType::Gs1DatabarExpanded => "gs1_databar_expanded",
+        }
+    }
+
+    /// Check that `data` can be encoded as this barcode type.
+    pub fn validate(&self, data: &str) -> Result<(), ValidationError> {
+        let barcode = self.name();
+        match self {
+            BarcodeType::UpcA => check_upc_ean(barcode, data, 11),
+            BarcodeType::UpcE => {
+                check_upc_ean(barcode, data, 11)?;
+                if !data.starts_with('0') {
+                    return Err(ValidationError::InvalidCharacter { barcode, character: data.chars().next().unwrap_or_default(), position: 0 });
+                }
+                Ok(())
+            },
+            BarcodeType::EAN13 | BarcodeType::JAN13 => check_upc_ean(barcode, data, 12),
+            BarcodeType::EAN8 | BarcodeType::JAN8 => check_upc_ean(barcode, data, 7),
+            BarcodeType::Code39 => {
+                check_not_empty(barcode, data)?;
+                let last = data.chars().count() - 1;
+                check_chars(barcode, data, |idx, c| c.is_ascii_digit() || c.is_ascii_uppercase() || " $%+-./".contains(c)
+                    || (c == '*' && (idx == 0 || (idx == last && data.starts_with('*')))))
+            },
+            BarcodeType::ITF => {
+                check_chars(barcode, data, |_, c| c.is_ascii_digit())?;
+                if data.is_empty() || !data.len().is_multiple_of(2) {
+                    return Err(ValidationError::InvalidLength { barcode, expected: "an even number of digits", actual: data.len() });
+                }
+                Ok(())
+            },
+            BarcodeType::CodaBar => {
+                if data.chars().count() < 3 {
+                    return Err(ValidationError::InvalidLength { barcode, expected: "a start character, data and a stop character", actual: data.chars().count() });
+                }
+                let last = data.chars().count() - 1;
+                check_chars(barcode, data, |idx, c| if idx == 0 || idx == last {
+                    "ABCDabcd".contains(c)
+                } else {
+                    c.is_ascii_digit() || "-$:/.+".contains(c)
+                })
+            },
+            BarcodeType::Code93 => {
+                check_not_empty(barcode, data)?;
+                check_chars(barcode, data, |_, c| c.is_ascii())
+            },
+            BarcodeType::Code128 => {
+                check_chars(barcode, data, |_, c| c.is_ascii())?;
+                if !(data.starts_with("{A") || data.starts_with("{B") || data.starts_with("{C")) {
+                    return Err(ValidationError::InvalidSyntax { barcode, reason: "data must begin with a start character: {A, {B or {C" });
+                }
+                check_escapes(barcode, data, "1234ABCS{")
+            },
+            BarcodeType::GS1_128 => check_gs1(barcode, data, "13()*{"),
+            BarcodeType::GS1DatabarOmnidirectional | BarcodeType::GS1DatabarTruncated => check_digits(barcode, data, 13, "13 digits"),
+            BarcodeType::GS1DatabarLimited => {
+                check_digits(barcode, data, 13, "13 digits")?;
+                if !(data.starts_with('0') || data.starts_with('1')) {
+                    return Err(ValidationError::InvalidCharacter { barcode, character: data.chars().next().unwrap_or_default(), position: 0 });
+                }
+                Ok(())
+            },
+            BarcodeType::Gs1DatabarExpanded => check_gs1(barcode, data, "1()"),
+        }
+    }
+}
+
+fn check_not_empty(barcode: &'static str, data: &str) -> Result<(), ValidationError> {
+    if data.is_empty() {
+        return Err(ValidationError::InvalidLength { barcode, expected: "at least one character", actual: 0 });
+    }
+    Ok(())
+}
+
+/// Check every character with `valid(position, character)`.
+fn check_chars<F: Fn(usize, char) -> bool>(barcode: &'static str, data: &str, valid: F) -> Result<(), ValidationError> {
+    match data.chars().enumerate().find(|(idx, c)| !valid(*idx, *c)) {
+        Some((position, character)) => Err(ValidationError::InvalidCharacter { barcode, character, position }),
+        None => Ok(())
+    }
+}
+
+fn check_digits(barcode: &'static str, data: &str, len: usize, expected: &'static str) -> Result<(), ValidationError> {
+    check_chars(barcode, data, |_, c| c.is_ascii_digit())?;
+    if data.len() != len {
+        return Err(ValidationError::InvalidLength { barcode, expected, actual: data.len() });
+    }
+    Ok(())
+}
+
+/// UPC, EAN and JAN codes take `len` digits, or `len + 1` digits including a check digit.
+fn check_upc_ean(barcode: &'static str, data: &str, len: usize) -> Result<(), ValidationError> {
+    check_chars(barcode, data, |_, c| c.is_ascii_digit())?;
+    if data.len() == len + 1 {
+        let expected = mod10_check_digit(&data[..len]).unwrap_or_default();
+        let actual = data.as_bytes()[len] - b'0';
+        if expected != actual {
+            return Err(ValidationError::CheckDigit { barcode, expected, actual });
+        }
+    } else if data.len() != len {
+        let expected = match len {
+            7 => "7 or 8 digits",
+            11 => "11 or 12 digits",
+            _ => "12 or 13 digits",
+        };
+        return Err(ValidationError::InvalidLength { barcode, expected, actual: data.len() });
+    }
+    Ok(())
+}
+
+/// Check that every `{` starts one of the allowed two character escapes.
+fn check_escapes(barcode: &'static str, data: &str, allowed: &str) -> Result<(), ValidationError> {
+    let mut chars = data.chars().enumerate();
+    while let Some((position, c)) = chars.next() {
+        if c == '{' {
+            match chars.next() {
+                Some((_, next)) if allowed.contains(next) => {},
+                Some((position, character)) => return Err(ValidationError::InvalidCharacter { barcode, character, position }),
+                None => return Err(ValidationError::InvalidCharacter { barcode, character: c, position })
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the application identifier syntax of GS1 data.
+/// Application identifiers are either enclosed in parentheses, as in `(01)04912345123459(10)ABC`,
+/// or written without them, in which case the data must begin with the digits of an application identifier.
+fn check_gs1(barcode: &'static str, data: &str, escapes: &str) -> Result<(), ValidationError> {
+    check_chars(barcode, data, |_, c| c.is_ascii() && !c.is_ascii_control())?;
+    check_escapes(barcode, data, escapes)?;
+
+    if !data.starts_with('(') {
+        if data.len() < 2 || !data.as_bytes()[..2].iter().all(u8::is_ascii_digit) {
+            return Err(ValidationError::InvalidSyntax { barcode, reason: "data must begin with an application identifier" });
+        }
+        return Ok(());
+    }
+
+    // with escapes removed, every "(" must be followed by a 2-4 digit AI, a ")" and some data
+    let mut unescaped = String::new();
+    let mut chars = data.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => { chars.next(); unescaped.push('_'); },
+            _ => unescaped.push(c)
+        }
+    }
+    for element in unescaped.split('(').skip(1) {
+        let (ai, value) = element.split_once(')')
+            .ok_or(ValidationError::InvalidSyntax { barcode, reason: "unclosed application identifier" })?;
+        if !(2..=4).contains(&ai.len()) || !ai.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ValidationError::InvalidSyntax { barcode, reason: "application identifiers must be 2 to 4 digits" });
+        }
+        if value.trim().is_empty() {
+            return Err(ValidationError::InvalidSyntax { barcode, reason: "application identifier has no data" });
+        }
+        if value.contains(')') {
+            return Err(ValidationError::InvalidSyntax { barcode, reason: "unmatched parenthesis, use {( or {) to encode one" });
+        }
+    }
+    Ok(())
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
 /// types of available 2D barcodes.
 /// Docs here are taken from the full XML spec: <https://files.support.epson.com/pdf/pos/bulk/epos-print_xml_um_en_revi.pdf>
@@ -156,6 +353,43 @@ pub enum SymbolType {
     DatamatrixRectangle16,
 }
 
+impl SymbolType {
+    /// The ePOS name of the symbol type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolType::PDF417 => "pdf417_standard",
+            SymbolType::PDF415Trunc => "pdf417_truncated",
+            SymbolType::QRCode1 => "qrcode_model_1",
+            SymbolType::QRCode2 => "qrcode_model_2",
+            SymbolType::MaxiCodeMode2 => "maxicode_mode_2",
+            SymbolType::MaxiCodeMode3 => "maxicode_mode_3",
+            SymbolType::MaxiCodeMode4 => "maxicode_mode_4",
+            SymbolType::MaxiCodeMode5 => "maxicode_mode_5",
+            SymbolType::MaxiCodeMode6 => "maxicode_mode_6",
+            SymbolType::GS1DatabarStacked => "gs1_databar_stacked",
+            SymbolType::GS1DatabarStackedOmnidirectional => "gs1_databar_stacked_omnidirectional",
+            SymbolType::GS1DatabarExpandedStacked => "gs1_databar_expanded_stacked",
+            SymbolType::AztecCodeFullRange => "azteccode_fullrange",
+            SymbolType::AztecCodeCompact => "azteccode_compact",
+            SymbolType::DatamatrixSquare => "datamatrix_square",
+            SymbolType::DatamatrixRectangle8 => "datamatrix_rectangle_8",
+            SymbolType::DatamatrixRectangle12 => "datamatrix_rectangle_12",
+            SymbolType::DatamatrixRectangle16 => "datamatrix_rectangle_16",
+        }
+    }
+}
+
+/// Return the postal code of a MaxiCode mode 2 or 3 primary message, skipping the optional `[)>\x1e01\x1dyy` message header.
+/// Separators may be given as control characters or as `\xnn` escapes.
+pub(crate) fn maxicode_postal_code(data: &str) -> String {
+    let data = data.replace("\\x1d", "\x1d").replace("\\x1D", "\x1d").replace("\\x1e", "\x1e").replace("\\x1E", "\x1e");
+    let primary = match data.strip_prefix("[)>\x1e01\x1d") {
+        Some(rest) => rest.get(2..).unwrap_or_default(),
+        None => &data
+    };
+    primary.split('\x1d').next().unwrap_or_default().to_string()
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 /// Specifies the HRI position for a barcode
 pub enum HRI {
@@ -169,39 +403,406 @@ pub enum HRI {
     Both
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 /// Error correction levels
 /// variants level_{0-8} are used by PDF417,
 /// while level_{l-h} are used by QRCode.
 /// Aztec codes will take a pure integer value ranging from 5-95
 pub enum ErrorCorrectionLevel {
-    #[serde(rename = "level_0")]
     Level0,
-    #[serde(rename = "level_1")]
     Level1,
-    #[serde(rename = "level_2")]
     Level2,
-    #[serde(rename = "level_3")]
     Level3,
-    #[serde(rename = "level_4")]
     Level4,
-    #[serde(rename = "level_5")]
     Level5,
-    #[serde(rename = "level_6")]
     Level6,
-    #[serde(rename = "level_7")]
     Level7,
-    #[serde(rename = "level_8")]
     Level8,
-    #[serde(rename = "level_l")]
     LevelL,
-    #[serde(rename = "level_m")]
     LevelM,
-    #[serde(rename = "level_q")]
     LevelQ,
-    #[serde(rename = "level_h")]
     LevelH,
-    #[serde(rename = "default")]
     Default,
+    /// Aztec error correction level, in percent
     Int(u32)
-}
\ No newline at end of file
+}
+
+const NAMED_LEVELS: [(ErrorCorrectionLevel, &str); 14] = [
+    (ErrorCorrectionLevel::Level0, "level_0"),
+    (ErrorCorrectionLevel::Level1, "level_1"),
+    (ErrorCorrectionLevel::Level2, "level_2"),
+    (ErrorCorrectionLevel::Level3, "level_3"),
+    (ErrorCorrectionLevel::Level4, "level_4"),
+    (ErrorCorrectionLevel::Level5, "level_5"),
+    (ErrorCorrectionLevel::Level6, "level_6"),
+    (ErrorCorrectionLevel::Level7, "level_7"),
+    (ErrorCorrectionLevel::Level8, "level_8"),
+    (ErrorCorrectionLevel::LevelL, "level_l"),
+    (ErrorCorrectionLevel::LevelM, "level_m"),
+    (ErrorCorrectionLevel::LevelQ, "level_q"),
+    (ErrorCorrectionLevel::LevelH, "level_h"),
+    (ErrorCorrectionLevel::Default, "default"),
+];
+
+impl ErrorCorrectionLevel {
+    /// Returns true for the PDF417 levels, level_0 to level_8.
+    pub fn is_pdf417(&self) -> bool {
+        matches!(self, ErrorCorrectionLevel::Level0 | ErrorCorrectionLevel::Level1 | ErrorCorrectionLevel::Level2
+            | ErrorCorrectionLevel::Level3 | ErrorCorrectionLevel::Level4 | ErrorCorrectionLevel::Level5
+            | ErrorCorrectionLevel::Level6 | ErrorCorrectionLevel::Level7 | ErrorCorrectionLevel::Level8)
+    }
+
+    /// Returns true for the QR Code levels, level_l to level_h.
+    pub fn is_qr(&self) -> bool {
+        matches!(self, ErrorCorrectionLevel::LevelL | ErrorCorrectionLevel::LevelM | ErrorCorrectionLevel::LevelQ | ErrorCorrectionLevel::LevelH)
+    }
+}
+
+impl Serialize for ErrorCorrectionLevel {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ErrorCorrectionLevel::Int(level) => serializer.serialize_str(&level.to_string()),
+            named => {
+                let name = NAMED_LEVELS.iter().find(|(level, _)| level == named).map(|(_, name)| *name).unwrap_or("default");
+                serializer.serialize_str(name)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCorrectionLevel {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        if let Some((level, _)) = NAMED_LEVELS.iter().find(|(_, name)| *name == raw) {
+            return Ok(*level);
+        }
+        raw.parse().map(ErrorCorrectionLevel::Int)
+            .map_err(|_| serde::de::Error::custom(format!("unknown error correction level {:?}", raw)))
+    }
+}
+
+/// A date in a GS1 date field, printed as YYMMDD.
+/// A day of 0 means the last day of the month, and is only meaningful for best before and expiry dates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gs1Date {
+    /// Full year, 2000-2099
+    pub year: u16,
+    /// Month, 1-12
+    pub month: u8,
+    /// Day of the month, 0-31
+    pub day: u8
+}
+
+impl Gs1Date {
+    pub fn new(year: u16, month: u8, day: u8) -> Gs1Date {
+        Gs1Date { year, month, day }
+    }
+}
+
+/// A GS1 application identifier (AI) and its data.
+/// GTIN, SSCC and GLN values are given without their check digit, which is calculated when the element string is built.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApplicationIdentifier {
+    /// (00) Serial shipping container code, 17 digits
+    Sscc(String),
+    /// (01) Global trade item number, up to 13 digits. Shorter GTIN-8, GTIN-12 and GTIN-13 numbers are padded with leading zeros.
+    Gtin(String),
+    /// (02) GTIN of the trade items contained in a logistic unit, up to 13 digits
+    ContentGtin(String),
+    /// (10) Batch or lot number, up to 20 characters
+    BatchLot(String),
+    /// (11) Production date
+    ProductionDate(Gs1Date),
+    /// (13) Packaging date
+    PackagingDate(Gs1Date),
+    /// (15) Best before date
+    BestBefore(Gs1Date),
+    /// (17) Expiration date
+    Expiry(Gs1Date),
+    /// (21) Serial number, up to 20 characters
+    SerialNumber(String),
+    /// (30) Variable count of items, up to 8 digits
+    VariableCount(u32),
+    /// (37) Count of trade items contained in a logistic unit, up to 8 digits
+    Count(u32),
+    /// (310n) Net weight in kilograms, as `value` × 10^-`decimals`, so 1.25 kg is `{ value: 1250, decimals: 3 }`.
+    /// Up to 6 digits and 9 decimal places.
+    NetWeightKg { value: u32, decimals: u8 },
+    /// (320n) Net weight in pounds, as `value` × 10^-`decimals`.
+    NetWeightLb { value: u32, decimals: u8 },
+    /// (392n) Amount payable in the local currency, as `value` × 10^-`decimals`. Up to 15 digits.
+    Price { value: u64, decimals: u8 },
+    /// (400) Customer's purchase order number, up to 30 characters
+    OrderNumber(String),
+    /// (410) Ship to global location number (GLN), 12 digits
+    ShipTo(String),
+    /// (414) GLN of a physical location, 12 digits
+    Location(String),
+    /// Any other AI. The data is passed through as is, and must include any check digit.
+    Other { ai: String, data: String }
+}
+
+impl ApplicationIdentifier {
+    /// Return the AI and its data, with check digits calculated and numbers padded to their fixed length.
+    pub fn encode(&self) -> Result<(String, String), ValidationError> {
+        let (ai, data) = match self {
+            ApplicationIdentifier::Sscc(num) => ("00".to_string(), with_check_digit("00", num, 17)?),
+            ApplicationIdentifier::Gtin(num) => ("01".to_string(), with_check_digit("01", num, 13)?),
+            ApplicationIdentifier::ContentGtin(num) => ("02".to_string(), with_check_digit("02", num, 13)?),
+            ApplicationIdentifier::BatchLot(text) => ("10".to_string(), check_cset82("10", text, 20)?),
+            ApplicationIdentifier::ProductionDate(date) => ("11".to_string(), encode_date("11", date)?),
+            ApplicationIdentifier::PackagingDate(date) => ("13".to_string(), encode_date("13", date)?),
+            ApplicationIdentifier::BestBefore(date) => ("15".to_string(), encode_date("15", date)?),
+            ApplicationIdentifier::Expiry(date) => ("17".to_string(), encode_date("17", date)?),
+            ApplicationIdentifier::SerialNumber(text) => ("21".to_string(), check_cset82("21", text, 20)?),
+            ApplicationIdentifier::VariableCount(count) => ("30".to_string(), encode_number("30", *count as u64, 8)?),
+            ApplicationIdentifier::Count(count) => ("37".to_string(), encode_number("37", *count as u64, 8)?),
+            ApplicationIdentifier::NetWeightKg { value, decimals } => (decimal_ai("310", *decimals)?, encode_fixed("310n", *value as u64, 6)?),
+            ApplicationIdentifier::NetWeightLb { value, decimals } => (decimal_ai("320", *decimals)?, encode_fixed("320n", *value as u64, 6)?),
+            ApplicationIdentifier::Price { value, decimals } => (decimal_ai("392", *decimals)?, encode_number("392n", *value, 15)?),
+            ApplicationIdentifier::OrderNumber(text) => ("400".to_string(), check_cset82("400", text, 30)?),
+            ApplicationIdentifier::ShipTo(num) => ("410".to_string(), with_check_digit("410", num, 12)?),
+            ApplicationIdentifier::Location(num) => ("414".to_string(), with_check_digit("414", num, 12)?),
+            ApplicationIdentifier::Other { ai, data } => {
+                if !(2..=4).contains(&ai.len()) || !ai.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(ValidationError::Gs1Element { ai: ai.clone(), reason: "application identifiers must be 2 to 4 digits" });
+                }
+                (ai.clone(), check_cset82(ai, data, 90)?)
+            }
+        };
+        Ok((ai, data))
+    }
+}
+
+/// Returns true if the AI has a predefined length, and so is never followed by an FNC1 separator.
+fn is_predefined_length(ai: &str) -> bool {
+    const PREFIXES: [&str; 22] = ["00", "01", "02", "03", "04", "11", "12", "13", "14", "15", "16", "17", "18", "19", "20",
+        "31", "32", "33", "34", "35", "36", "41"];
+    PREFIXES.iter().any(|prefix| ai.starts_with(prefix))
+}
+
+fn gs1_error(ai: &str, reason: &'static str) -> ValidationError {
+    ValidationError::Gs1Element { ai: ai.to_string(), reason }
+}
+
+fn with_check_digit(ai: &str, digits: &str, len: usize) -> Result<String, ValidationError> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(gs1_error(ai, "must only contain digits"));
+    }
+    if digits.len() > len {
+        return Err(gs1_error(ai, "too many digits, leave out the check digit"));
+    }
+    let padded = format!("{:0>len$}", digits);
+    let check = mod10_check_digit(&padded).unwrap_or_default();
+    Ok(format!("{}{}", padded, check))
+}
+
+fn encode_number(ai: &str, value: u64, max_digits: usize) -> Result<String, ValidationError> {
+    let text = value.to_string();
+    if text.len() > max_digits {
+        return Err(gs1_error(ai, "value has too many digits"));
+    }
+    Ok(text)
+}
+
+fn encode_fixed(ai: &str, value: u64, len: usize) -> Result<String, ValidationError> {
+    Ok(format!("{:0>len$}", encode_number(ai, value, len)?))
+}
+
+fn decimal_ai(prefix: &str, decimals: u8) -> Result<String, ValidationError> {
+    if decimals > 9 {
+        return Err(gs1_error(&format!("{}n", prefix), "at most 9 decimal places"));
+    }
+    Ok(format!("{}{}", prefix, decimals))
+}
+
+fn encode_date(ai: &str, date: &Gs1Date) -> Result<String, ValidationError> {
+    if !(2000..=2099).contains(&date.year) {
+        return Err(gs1_error(ai, "year must be from 2000 to 2099"));
+    }
+    if !(1..=12).contains(&date.month) || date.day > 31 {
+        return Err(gs1_error(ai, "invalid month or day"));
+    }
+    Ok(format!("{:02}{:02}{:02}", date.year % 100, date.month, date.day))
+}
+
+/// Check the data only uses GS1 character set 82, the printable characters allowed in alphanumeric AIs.
+fn check_cset82(ai: &str, data: &str, max_len: usize) -> Result<String, ValidationError> {
+    if data.is_empty() || data.len() > max_len {
+        return Err(gs1_error(ai, "data is empty or too long"));
+    }
+    let valid = |c: char| c.is_ascii_alphanumeric() || "!\"%&'()*+,-./:;<=>?_".contains(c);
+    if !data.chars().all(valid) {
+        return Err(gs1_error(ai, "data contains a character outside GS1 character set 82"));
+    }
+    Ok(data.to_string())
+}
+
+/// Builds GS1 element strings from application identifiers.
+/// Each AI is written in parentheses, which are printed in the human readable text but not encoded,
+/// and an FNC1 separator (`{1`) is inserted after every variable length AI that is not the last one.
+///
+/// ```
+/// use epos_rs::barcodes::{ApplicationIdentifier, Gs1Date, Gs1ElementString};
+///
+/// let text = Gs1ElementString::new()
+///     .push(ApplicationIdentifier::Gtin("491234512345".to_string()))
+///     .push(ApplicationIdentifier::BatchLot("A12".to_string()))
+///     .push(ApplicationIdentifier::Expiry(Gs1Date::new(2025, 12, 31)))
+///     .to_gs1_128()
+///     .unwrap();
+/// assert_eq!(text, "(01)04912345123459(10)A12{1(17)251231");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Gs1ElementString {
+    elements: Vec<ApplicationIdentifier>
+}
+
+impl Gs1ElementString {
+    pub fn new() -> Gs1ElementString {
+        Gs1ElementString::default()
+    }
+
+    /// Append an application identifier.
+    pub fn push(mut self, element: ApplicationIdentifier) -> Self {
+        self.elements.push(element);
+        self
+    }
+
+    /// The application identifiers, in order.
+    pub fn elements(&self) -> &[ApplicationIdentifier] {
+        &self.elements
+    }
+
+    /// Build the text for `BarcodeType::GS1_128`.
+    pub fn to_gs1_128(&self) -> Result<String, ValidationError> {
+        self.build("()*")
+    }
+
+    /// Build the text for `BarcodeType::Gs1DatabarExpanded` or `SymbolType::GS1DatabarExpandedStacked`.
+    pub fn to_databar_expanded(&self) -> Result<String, ValidationError> {
+        self.build("()")
+    }
+
+    /// Build the element string, escaping the given characters in AI data with `{`.
+    fn build(&self, escaped: &str) -> Result<String, ValidationError> {
+        if self.elements.is_empty() {
+            return Err(ValidationError::Gs1Element { ai: String::new(), reason: "no application identifiers" });
+        }
+        let mut out = String::new();
+        for (idx, element) in self.elements.iter().enumerate() {
+            let (ai, data) = element.encode()?;
+            out.push('(');
+            out.push_str(&ai);
+            out.push(')');
+            for c in data.chars() {
+                if escaped.contains(c) {
+                    out.push('{');
+                }
+                out.push(c);
+            }
+            if !is_predefined_length(&ai) && idx + 1 < self.elements.len() {
+                out.push_str("{1");
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ValidationError;
+
+    use super::{mod10_check_digit, ApplicationIdentifier, BarcodeType, Gs1Date, Gs1ElementString};
+
+    #[test]
+    fn test_check_digit() {
+        assert_eq!(mod10_check_digit("490123456789"), Some(4));
+        assert_eq!(mod10_check_digit("03600029145"), Some(2));
+        assert_eq!(mod10_check_digit("1234567"), Some(0));
+        assert_eq!(mod10_check_digit("12a"), None);
+        assert_eq!(mod10_check_digit(""), None);
+    }
+
+    #[test]
+    fn test_upc_ean() {
+        assert!(BarcodeType::UpcA.validate("03600029145").is_ok());
+        assert!(BarcodeType::UpcA.validate("036000291452").is_ok());
+        assert_eq!(BarcodeType::UpcA.validate("036000291453").unwrap_err(),
+            ValidationError::CheckDigit { barcode: "upc_a", expected: 2, actual: 3 });
+        assert_eq!(BarcodeType::EAN13.validate("49012345678").unwrap_err(),
+            ValidationError::InvalidLength { barcode: "ean13", expected: "12 or 13 digits", actual: 11 });
+        assert_eq!(BarcodeType::EAN8.validate("1234-67").unwrap_err(),
+            ValidationError::InvalidCharacter { barcode: "ean8", character: '-', position: 4 });
+        assert!(BarcodeType::UpcE.validate("12345678901").is_err());
+    }
+
+    #[test]
+    fn test_character_sets() {
+        assert!(BarcodeType::Code39.validate("*ABC-123 $%*").is_ok());
+        assert_eq!(BarcodeType::Code39.validate("abc").unwrap_err(),
+            ValidationError::InvalidCharacter { barcode: "code39", character: 'a', position: 0 });
+        assert!(BarcodeType::Code39.validate("AB*C").is_err());
+
+        assert!(BarcodeType::CodaBar.validate("A40156B").is_ok());
+        assert_eq!(BarcodeType::CodaBar.validate("401567").unwrap_err(),
+            ValidationError::InvalidCharacter { barcode: "codabar", character: '4', position: 0 });
+
+        assert!(BarcodeType::ITF.validate("1234").is_ok());
+        assert!(BarcodeType::ITF.validate("123").is_err());
+    }
+
+    #[test]
+    fn test_code128() {
+        assert!(BarcodeType::Code128.validate("{Babc{C12{{").is_ok());
+        assert!(matches!(BarcodeType::Code128.validate("abc"), Err(ValidationError::InvalidSyntax { .. })));
+        assert_eq!(BarcodeType::Code128.validate("{Bab{x").unwrap_err(),
+            ValidationError::InvalidCharacter { barcode: "code128", character: 'x', position: 5 });
+    }
+
+    #[test]
+    fn test_gs1() {
+        assert!(BarcodeType::GS1_128.validate("(01)04912345123459(10)ABC{1").is_ok());
+        assert!(BarcodeType::GS1_128.validate("(01)0491234512345*").is_ok());
+        assert!(BarcodeType::GS1_128.validate("0104912345123459").is_ok());
+        assert!(BarcodeType::GS1_128.validate("(1a)123").is_err());
+        assert!(BarcodeType::GS1_128.validate("(01").is_err());
+        assert!(BarcodeType::GS1_128.validate("(10)").is_err());
+        assert!(BarcodeType::Gs1DatabarExpanded.validate("(10)A{*").is_err());
+
+        assert!(BarcodeType::GS1DatabarOmnidirectional.validate("0491234512345").is_ok());
+        assert!(BarcodeType::GS1DatabarLimited.validate("2491234512345").is_err());
+    }
+
+    #[test]
+    fn test_gs1_element_string() {
+        let weighed = Gs1ElementString::new()
+            .push(ApplicationIdentifier::Gtin("952123454321".to_string()))
+            .push(ApplicationIdentifier::NetWeightKg { value: 1250, decimals: 3 })
+            .push(ApplicationIdentifier::BatchLot("LOT(7)*".to_string()))
+            .push(ApplicationIdentifier::SerialNumber("42".to_string()))
+            .push(ApplicationIdentifier::BestBefore(Gs1Date::new(2026, 3, 0)));
+        let text = weighed.to_gs1_128().unwrap();
+        assert_eq!(text, "(01)09521234543213(3103)001250(10)LOT{(7{){*{1(21)42{1(15)260300");
+        assert!(BarcodeType::GS1_128.validate(&text).is_ok());
+        let text = weighed.to_databar_expanded().unwrap();
+        assert_eq!(text, "(01)09521234543213(3103)001250(10)LOT{(7{)*{1(21)42{1(15)260300");
+
+        let sscc = Gs1ElementString::new().push(ApplicationIdentifier::Sscc("35412345678901234".to_string()))
+            .push(ApplicationIdentifier::Price { value: 1999, decimals: 2 });
+        assert_eq!(sscc.to_databar_expanded().unwrap(), "(00)354123456789012347(3922)1999");
+    }
+
+    #[test]
+    fn test_gs1_errors() {
+        let err = Gs1ElementString::new().push(ApplicationIdentifier::Gtin("04912345123459".to_string())).to_gs1_128().unwrap_err();
+        assert!(matches!(err, ValidationError::Gs1Element { ai, .. } if ai == "01"));
+        let err = Gs1ElementString::new().push(ApplicationIdentifier::BatchLot("ü".to_string())).to_gs1_128().unwrap_err();
+        assert!(matches!(err, ValidationError::Gs1Element { ai, .. } if ai == "10"));
+        let err = Gs1ElementString::new().push(ApplicationIdentifier::Expiry(Gs1Date::new(2025, 13, 1))).to_gs1_128().unwrap_err();
+        assert!(matches!(err, ValidationError::Gs1Element { ai, .. } if ai == "17"));
+        let err = Gs1ElementString::new().push(ApplicationIdentifier::NetWeightKg { value: 1_000_000, decimals: 0 }).to_gs1_128().unwrap_err();
+        assert!(matches!(err, ValidationError::Gs1Element { ai, .. } if ai == "310n"));
+        assert!(Gs1ElementString::new().to_gs1_128().is_err());
+    }
+}
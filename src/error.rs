@@ -8,12 +8,101 @@ use crate::status::Response;
 pub enum EPOSError {
     #[error("error serializing XML")]
     SerializeError(#[from] quick_xml::DeError),
+    #[error("error writing XML")]
+    XmlError(#[from] quick_xml::Error),
     #[error("Network request error")]
     NetworkError(#[from] reqwest::Error),
+    #[error("unexpected response from the printer")]
+    InvalidResponseError(#[source] quick_xml::DeError),
     #[error("invalid header")]
     InvalidHeaderError(#[from] reqwest::header::InvalidHeaderValue),
     #[error("error sending document: {status}")]
     ResponseError{status: Response},
     #[error("error parsing URL")]
-    ParseError(#[from] url::ParseError)
-}
\ No newline at end of file
+    ParseError(#[from] url::ParseError),
+    #[error("invalid document: {0}")]
+    ValidationError(#[from] ValidationError),
+    #[error("error reading ePOS-Print XML at byte {position}: {reason}")]
+    DocumentError{position: usize, reason: String},
+    #[error("connection error")]
+    IoError(#[from] std::io::Error),
+    #[error("not supported by this transport: {0}")]
+    UnsupportedError(&'static str),
+    #[error("no printer in the pool is tagged {0:?}")]
+    NoPrinterError(String),
+    #[error("invalid certificate: {0}")]
+    CertificateError(&'static str),
+    #[cfg(feature = "image")]
+    #[error("error loading image")]
+    ImageError(#[from] image::ImageError)
+}
+
+impl EPOSError {
+    /// Classify the error by what it takes to resolve it.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            EPOSError::NetworkError(_) | EPOSError::IoError(_) | EPOSError::InvalidResponseError(_) => ErrorKind::Retryable,
+            EPOSError::ResponseError { status } => status.code.kind(),
+            EPOSError::SerializeError(_) | EPOSError::XmlError(_) | EPOSError::InvalidHeaderError(_)
+                | EPOSError::ParseError(_) | EPOSError::ValidationError(_) | EPOSError::DocumentError { .. }
+                | EPOSError::UnsupportedError(_) | EPOSError::NoPrinterError(_) | EPOSError::CertificateError(_) => ErrorKind::Programming,
+            #[cfg(feature = "image")]
+            EPOSError::ImageError(_) => ErrorKind::Programming
+        }
+    }
+
+    /// Returns true if the same request may succeed when sent again.
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Retryable
+    }
+
+    /// Returns true if someone has to attend to the printer before the request can succeed.
+    pub fn needs_operator(&self) -> bool {
+        self.kind() == ErrorKind::Operator
+    }
+
+    /// Returns true if the request itself is wrong, and will never succeed.
+    pub fn is_programming_error(&self) -> bool {
+        self.kind() == ErrorKind::Programming
+    }
+}
+
+/// Broad classes of errors, used to decide how to react to a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A transient failure, the request can be sent again
+    Retryable,
+    /// The printer needs attention, such as closing the cover or replacing the paper
+    Operator,
+    /// The request is invalid
+    Programming
+}
+
+/// Errors found while building a document, before it is sent to the printer.
+#[derive(Error, Debug, PartialEq)]
+pub enum ValidationError {
+    #[error("vline-end at x={x} has no matching vline-begin")]
+    UnmatchedVlineEnd{x: u16},
+    #[error("vline-begin at x={x} is never ended")]
+    UnclosedVline{x: u16},
+    #[error("feed to next_tof requires a label layout")]
+    NextTofWithoutLabel,
+    #[error("{field} must be between {min} and {max}, got {value}")]
+    OutOfRange{field: &'static str, value: i64, min: i64, max: i64},
+    #[error("image buffer has {actual} bytes, expected {expected}")]
+    ImageBufferSize{expected: usize, actual: usize},
+    #[error("{barcode} data must be {expected}, got {actual} characters")]
+    InvalidLength{barcode: &'static str, expected: &'static str, actual: usize},
+    #[error("{barcode} data contains invalid character {character:?} at position {position}")]
+    InvalidCharacter{barcode: &'static str, character: char, position: usize},
+    #[error("{barcode} check digit is {actual}, expected {expected}")]
+    CheckDigit{barcode: &'static str, expected: u8, actual: u8},
+    #[error("{barcode} data is malformed: {reason}")]
+    InvalidSyntax{barcode: &'static str, reason: &'static str},
+    #[error("invalid {field} for {symbol}: {reason}")]
+    InvalidParameter{symbol: &'static str, field: &'static str, reason: &'static str},
+    #[error("invalid GS1 element ({ai}): {reason}")]
+    Gs1Element{ai: String, reason: &'static str},
+    #[error("print job ID must be 1 to 30 letters, digits, '_', '.' or '-', got {0:?}")]
+    InvalidJobId(String)
+}
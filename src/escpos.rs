@@ -0,0 +1,602 @@
+//! A raw ESC/POS backend, for printers that accept ESC/POS on TCP port 9100 but don't run the ePOS web service.
+//!
+//! The backend is selected by giving `Builder::new` a `tcp://` address, such as `"tcp://192.168.1.50:9100"`,
+//! or by passing an `EscPosTransport` to `Builder::with_transport`.
+//! Documents are built and printed the same way as with the ePOS backend: normal mode items are encoded as ESC/POS commands,
+//! and printer status is read back with the `DLE EOT` real-time status commands.
+//!
+//! Some items have no ESC/POS equivalent. Page mode, vertical lines, sounds, label layouts, feeds to a label position
+//! and `Raw` elements are rejected with `EPOSError::UnsupportedError`.
+//! Text is sent as ASCII; other characters are printed as `?`. Use `Command::code_page` and `Command::new` to print in other code pages.
+
+use std::time::Duration;
+
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+use reqwest::IntoUrl;
+use url::{Host, Url};
+
+use crate::{barcodes::{BarcodeType, ErrorCorrectionLevel, HRI, SymbolType}, error::EPOSError, formatters::{Align, CutType, Drawer, Font, PulseTime, Style},
+    normal::{Hline, NormalElement}, status::{EposCode, PrinterStatus, Response}, universal::{Barcode, Feed, Image, Layout, Symbol, Text}, transport::{EnumBody, Request, Transport, TransportFuture}};
+
+/// URL scheme that selects the ESC/POS backend
+pub const SCHEME: &str = "tcp";
+/// Port used when the address doesn't specify one
+pub const DEFAULT_PORT: u16 = 9100;
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+
+/// `DLE EOT n` for printer, offline cause, error cause and paper sensor status, in that order.
+const STATUS_QUERY: [u8; 12] = [0x10, 0x04, 0x01, 0x10, 0x04, 0x02, 0x10, 0x04, 0x03, 0x10, 0x04, 0x04];
+
+/// Encode a normal mode document as ESC/POS, starting with `ESC @` to initialize the printer.
+pub fn encode(layout: Option<&Layout>, items: &[NormalElement]) -> Result<Vec<u8>, EPOSError> {
+    if layout.is_some() {
+        return Err(EPOSError::UnsupportedError("paper layouts"));
+    }
+    let mut encoder = Encoder { out: vec![ESC, b'@'], width: 1, height: 1 };
+    for item in items {
+        encoder.element(item)?;
+    }
+    Ok(encoder.out)
+}
+
+/// Decode the replies to `DLE EOT 1` to `DLE EOT 4` into a status bitmask.
+pub fn decode_status(reply: [u8; 4]) -> PrinterStatus {
+    let [printer, offline, error, paper] = reply;
+    let mut status = PrinterStatus::empty();
+    let bits = [
+        (printer & 0x04 != 0, PrinterStatus::DRAWER_KICK),
+        (printer & 0x08 != 0, PrinterStatus::OFFLINE),
+        (printer & 0x20 != 0, PrinterStatus::WAIT_ONLINE),
+        (printer & 0x40 != 0 || offline & 0x08 != 0, PrinterStatus::PAPER_FEED),
+        (offline & 0x04 != 0, PrinterStatus::COVER_OPEN),
+        (offline & 0x20 != 0 || paper & 0x60 != 0, PrinterStatus::RECEIPT_END),
+        (error & 0x04 != 0, PrinterStatus::MECHANICAL_ERROR),
+        (error & 0x08 != 0, PrinterStatus::AUTOCUTTER_ERROR),
+        (error & 0x20 != 0, PrinterStatus::UNRECOVERABLE_ERROR),
+        (error & 0x40 != 0, PrinterStatus::AUTO_RECOVERABLE_ERROR),
+        (paper & 0x0c != 0, PrinterStatus::RECEIPT_NEAR_END),
+    ];
+    for (set, flag) in bits {
+        if set {
+            status.insert(flag);
+        }
+    }
+    status
+}
+
+/// Build a response like the one the ePOS web service sends for the given status.
+fn response(status: PrinterStatus, printed: bool) -> Response {
+    let causes = [
+        (PrinterStatus::UNRECOVERABLE_ERROR, EposCode::Unrecoverable),
+        (PrinterStatus::MECHANICAL_ERROR, EposCode::Mechanical),
+        (PrinterStatus::AUTOCUTTER_ERROR, EposCode::Cutter),
+        (PrinterStatus::COVER_OPEN, EposCode::CoverOpen),
+        (PrinterStatus::RECEIPT_END, EposCode::ReceiptEmpty),
+        (PrinterStatus::AUTO_RECOVERABLE_ERROR, EposCode::AutoRecoverable),
+    ];
+    let code = causes.into_iter().find(|(flag, _)| status.contains(*flag)).map(|(_, code)| code).unwrap_or_default();
+    let success = code == EposCode::Empty && !status.contains(PrinterStatus::OFFLINE);
+    let status = if printed && success { status | PrinterStatus::PRINT_SUCCESS } else { status };
+    Response { success, code, status: status.bits(), ..Default::default() }
+}
+
+/// Sends documents as ESC/POS to a printer's raw TCP port.
+/// The device ID is ignored, and the request's timeout, in milliseconds, is used as the network timeout. Print job IDs are not supported.
+#[derive(Clone, Debug)]
+pub struct EscPosTransport {
+    endpoint: Url
+}
+
+impl EscPosTransport {
+    /// Create a transport for the printer at the given address, such as `"tcp://192.168.1.50:9100"`.
+    /// The port defaults to `DEFAULT_PORT`.
+    pub fn new<U: IntoUrl>(endpoint: U) -> Result<EscPosTransport, EPOSError> {
+        Ok(EscPosTransport { endpoint: endpoint.into_url()? })
+    }
+
+    /// Send a document and read back the printer status.
+    /// If the printer doesn't answer the status query, the document is assumed to have been printed, and the status is left empty.
+    async fn print(&self, layout: Option<&Layout>, items: &[NormalElement], timeout: i32) -> Result<Response, EPOSError> {
+        let data = encode(layout, items)?;
+        match exchange(&data, timeout, &self.endpoint).await? {
+            Some(reply) => Ok(response(decode_status(reply), true)),
+            None => Ok(Response { success: true, code: EposCode::Empty, ..Default::default() })
+        }
+    }
+
+    /// Read the printer status.
+    async fn status(&self, timeout: i32) -> Result<Response, EPOSError> {
+        match exchange(&[], timeout, &self.endpoint).await? {
+            Some(reply) => Ok(response(decode_status(reply), false)),
+            None => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "no reply to status query").into())
+        }
+    }
+}
+
+impl Transport for EscPosTransport {
+    fn send<'a>(&'a self, request: Request<'a>) -> TransportFuture<'a> {
+        Box::pin(async move {
+            if request.job_id.is_some() {
+                return Err(EPOSError::UnsupportedError("print job IDs"));
+            }
+            match request.body {
+                EnumBody::NoPage { layout, body } => self.print(layout, body, request.timeout).await,
+                EnumBody::Page { .. } => Err(EPOSError::UnsupportedError("page mode")),
+                EnumBody::Empty => self.status(request.timeout).await
+            }
+        })
+    }
+
+    fn supports_job_ids(&self) -> bool {
+        false
+    }
+}
+
+/// Write `data` followed by a status query, and return the reply. `timeout` is in milliseconds.
+async fn exchange(data: &[u8], timeout: i32, endpoint: &Url) -> Result<Option<[u8; 4]>, EPOSError> {
+    let host = match endpoint.host() {
+        Some(Host::Domain(domain)) => domain.to_string(),
+        Some(Host::Ipv4(addr)) => addr.to_string(),
+        Some(Host::Ipv6(addr)) => addr.to_string(),
+        None => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "address has no host").into())
+    };
+    let port = endpoint.port().unwrap_or(DEFAULT_PORT);
+    let timeout = Duration::from_millis(timeout.max(1) as u64);
+    let timed_out = |_| std::io::Error::new(std::io::ErrorKind::TimedOut, "connection timed out");
+
+    let mut stream = tokio::time::timeout(timeout, TcpStream::connect((host.as_str(), port))).await.map_err(timed_out)??;
+    let mut request = data.to_vec();
+    request.extend_from_slice(&STATUS_QUERY);
+    tokio::time::timeout(timeout, stream.write_all(&request)).await.map_err(timed_out)??;
+
+    let mut reply = [0u8; 4];
+    match tokio::time::timeout(timeout, stream.read_exact(&mut reply)).await {
+        Ok(Ok(_)) => Ok(Some(reply)),
+        Ok(Err(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Ok(Err(err)) => Err(err.into()),
+        Err(_) => Ok(None)
+    }
+}
+
+/// Tracks the character size, which `GS !` sets for both directions at once.
+struct Encoder {
+    out: Vec<u8>,
+    width: u8,
+    height: u8
+}
+
+impl Encoder {
+    fn element(&mut self, item: &NormalElement) -> Result<(), EPOSError> {
+        match item {
+            NormalElement::Text(text) => self.text(text),
+            NormalElement::Feed(feed) => self.feed(feed)?,
+            NormalElement::Barcode(barcode) => self.barcode(barcode)?,
+            NormalElement::Symbol(symbol) => self.symbol(symbol)?,
+            NormalElement::Image(image) => self.image(image)?,
+            NormalElement::Command(command) => {
+                let bytes = command.to_bytes().ok_or(EPOSError::UnsupportedError("commands that are not valid hex"))?;
+                self.out.extend_from_slice(&bytes);
+            },
+            NormalElement::Cut(cut) => self.out.extend_from_slice(match cut.cut_type {
+                CutType::NoFeed => &[GS, b'V', 1],
+                CutType::Feed => &[GS, b'V', 66, 0],
+                CutType::Reserve => &[GS, b'V', 104, 0]
+            }),
+            NormalElement::Hline(line) => self.hline(line),
+            NormalElement::Pulse(pulse) => {
+                let pin = match pulse.drawer { Some(Drawer::Drawer2) => 1, _ => 0 };
+                // pulse times are given in units of 2 ms
+                let time = match pulse.time {
+                    Some(PulseTime::Pulse200) => 100,
+                    Some(PulseTime::Pulse300) => 150,
+                    Some(PulseTime::Pulse400) => 200,
+                    Some(PulseTime::Pulse500) => 250,
+                    _ => 50
+                };
+                self.out.extend_from_slice(&[ESC, b'p', pin, time, time]);
+            },
+            NormalElement::Recovery(_) => self.out.extend_from_slice(&[0x10, 0x05, 0x01]),
+            NormalElement::Reset(_) => {
+                self.out.extend_from_slice(&[ESC, b'@']);
+                (self.width, self.height) = (1, 1);
+            },
+            NormalElement::VlineBegin(_) | NormalElement::VlineEnd(_) => return Err(EPOSError::UnsupportedError("vertical lines")),
+            NormalElement::Sound(_) => return Err(EPOSError::UnsupportedError("sounds")),
+            NormalElement::Raw(_) => return Err(EPOSError::UnsupportedError("raw ePOS-Print elements"))
+        }
+        Ok(())
+    }
+
+    fn align(&mut self, align: Option<&Align>) {
+        if let Some(align) = align {
+            let n = match align { Align::Left => 0, Align::Center => 1, Align::Right => 2 };
+            self.out.extend_from_slice(&[ESC, b'a', n]);
+        }
+    }
+
+    fn text(&mut self, text: &Text) {
+        if let Some(font) = &text.font {
+            let n = match font { Font::FontA => 0, Font::FontB => 1, Font::FontC => 2, Font::FontD => 3, Font::FontE => 4 };
+            self.out.extend_from_slice(&[ESC, b'M', n]);
+        }
+        if text.width.is_some() || text.height.is_some() || text.double_width.is_some() || text.double_height.is_some() {
+            if let Some(width) = text.width.or(text.double_width.map(|dw| if dw { 2 } else { 1 })) {
+                self.width = width.clamp(1, 8);
+            }
+            if let Some(height) = text.height.or(text.double_height.map(|dh| if dh { 2 } else { 1 })) {
+                self.height = height.clamp(1, 8);
+            }
+            self.out.extend_from_slice(&[GS, b'!', (self.width - 1) << 4 | (self.height - 1)]);
+        }
+        if let Some(underline) = text.underline {
+            self.out.extend_from_slice(&[ESC, b'-', underline as u8]);
+        }
+        if let Some(emph) = text.emph {
+            self.out.extend_from_slice(&[ESC, b'E', emph as u8]);
+        }
+        if let Some(smoothing) = text.smoothing {
+            self.out.extend_from_slice(&[GS, b'b', smoothing as u8]);
+        }
+        self.align(text.align.as_ref());
+        self.out.extend(text.text.chars().map(|c| if c.is_ascii() { c as u8 } else { b'?' }));
+    }
+
+    fn feed(&mut self, feed: &Feed) -> Result<(), EPOSError> {
+        if feed.pos.is_some() {
+            return Err(EPOSError::UnsupportedError("feeding to a label position"));
+        }
+        if let Some(spacing) = feed.linespc {
+            self.out.extend_from_slice(&[ESC, b'3', spacing]);
+        }
+        if let Some(unit) = feed.unit {
+            self.out.extend_from_slice(&[ESC, b'J', unit]);
+        } else if let Some(lines) = feed.line {
+            self.out.extend_from_slice(&[ESC, b'd', lines]);
+        } else if feed.linespc.is_none() {
+            self.out.push(b'\n');
+        }
+        Ok(())
+    }
+
+    /// ESC/POS has no line command in standard mode, so lines are printed as a raster image.
+    fn hline(&mut self, line: &Hline) {
+        let (x1, x2) = (line.x1.min(line.x2) as usize, line.x1.max(line.x2) as usize);
+        let thickness = match line.style {
+            Some(Style::Medium | Style::MediumDouble) => 2,
+            Some(Style::Thick | Style::ThickDouble) => 3,
+            _ => 1
+        };
+        let double = matches!(line.style, Some(Style::ThinDouble | Style::MediumDouble | Style::ThickDouble));
+        let rows: Vec<bool> = if double {
+            [true, false, true].iter().flat_map(|row| std::iter::repeat_n(*row, thickness)).collect()
+        } else {
+            vec![true; thickness]
+        };
+
+        let row_bytes = (x2 + 1).div_ceil(8);
+        let mut row = vec![0u8; row_bytes];
+        for x in x1..=x2 {
+            row[x / 8] |= 0x80 >> (x % 8);
+        }
+        let raster: Vec<u8> = rows.iter().flat_map(|set| if *set { row.clone() } else { vec![0; row_bytes] }).collect();
+        self.raster(&raster, row_bytes, rows.len());
+    }
+
+    /// Print a packed raster with `GS v 0`.
+    fn raster(&mut self, raster: &[u8], row_bytes: usize, height: usize) {
+        self.out.extend_from_slice(&[GS, b'v', b'0', 0, row_bytes as u8, (row_bytes >> 8) as u8, height as u8, (height >> 8) as u8]);
+        self.out.extend_from_slice(raster);
+    }
+
+    fn image(&mut self, image: &Image) -> Result<(), EPOSError> {
+        let (width, height) = (image.width().max(0) as usize, image.height().max(0) as usize);
+        let row_bytes = width.div_ceil(8);
+        let raster = image.to_raster().filter(|raster| raster.len() == row_bytes * height)
+            .ok_or(EPOSError::UnsupportedError("images with invalid raster data"))?;
+        if row_bytes > 0xffff || height > 0xffff {
+            return Err(EPOSError::UnsupportedError("images larger than 65535 dots"));
+        }
+        self.raster(&raster, row_bytes, height);
+        Ok(())
+    }
+
+    fn barcode(&mut self, barcode: &Barcode) -> Result<(), EPOSError> {
+        let m = match barcode.barcode_type() {
+            BarcodeType::UpcA => 65,
+            BarcodeType::UpcE => 66,
+            BarcodeType::EAN13 | BarcodeType::JAN13 => 67,
+            BarcodeType::EAN8 | BarcodeType::JAN8 => 68,
+            BarcodeType::Code39 => 69,
+            BarcodeType::ITF => 70,
+            BarcodeType::CodaBar => 71,
+            BarcodeType::Code93 => 72,
+            BarcodeType::Code128 => 73,
+            BarcodeType::GS1_128 => 74,
+            BarcodeType::GS1DatabarOmnidirectional => 75,
+            BarcodeType::GS1DatabarTruncated => 76,
+            BarcodeType::GS1DatabarLimited => 77,
+            BarcodeType::Gs1DatabarExpanded => 78
+        };
+        let mut data = unescape(barcode.text());
+        if matches!(barcode.barcode_type(), BarcodeType::GS1_128 | BarcodeType::Gs1DatabarExpanded) {
+            data = gs1_data(&data)?;
+        }
+        if data.len() > 255 {
+            return Err(EPOSError::UnsupportedError("barcodes with more than 255 bytes of data"));
+        }
+
+        if let Some(height) = barcode.height() {
+            self.out.extend_from_slice(&[GS, b'h', height]);
+        }
+        if let Some(width) = barcode.width() {
+            self.out.extend_from_slice(&[GS, b'w', width]);
+        }
+        if let Some(hri) = barcode.hri() {
+            let n = match hri { HRI::None => 0, HRI::Above => 1, HRI::Below => 2, HRI::Both => 3 };
+            self.out.extend_from_slice(&[GS, b'H', n]);
+        }
+        if let Some(font) = barcode.font() {
+            self.out.extend_from_slice(&[GS, b'f', if matches!(font, Font::FontB) { 1 } else { 0 }]);
+        }
+        self.align(barcode.align());
+        self.out.extend_from_slice(&[GS, b'k', m, data.len() as u8]);
+        self.out.extend_from_slice(&data);
+        Ok(())
+    }
+
+    /// Send a `GS ( k` 2D code function: symbol type `cn`, function `fun`, then `params`.
+    fn symbol_fn(&mut self, cn: u8, fun: u8, params: &[u8]) {
+        let len = params.len() + 2;
+        self.out.extend_from_slice(&[GS, b'(', b'k', len as u8, (len >> 8) as u8, cn, fun]);
+        self.out.extend_from_slice(params);
+    }
+
+    fn symbol(&mut self, symbol: &Symbol) -> Result<(), EPOSError> {
+        let data = unescape(&symbol.text);
+        if data.len() > 0xffff - 3 {
+            return Err(EPOSError::UnsupportedError("symbols with more than 65532 bytes of data"));
+        }
+        let cn = match symbol.symbol_type {
+            SymbolType::PDF417 | SymbolType::PDF415Trunc => {
+                if let Some(size) = symbol.size { self.symbol_fn(48, 65, &[size]); }
+                if let Some(width) = symbol.width { self.symbol_fn(48, 67, &[width]); }
+                if let Some(height) = symbol.height { self.symbol_fn(48, 68, &[height]); }
+                let level = match symbol.level {
+                    Some(ErrorCorrectionLevel::Level0) => Some(0),
+                    Some(ErrorCorrectionLevel::Level1) => Some(1),
+                    Some(ErrorCorrectionLevel::Level2) => Some(2),
+                    Some(ErrorCorrectionLevel::Level3) => Some(3),
+                    Some(ErrorCorrectionLevel::Level4) => Some(4),
+                    Some(ErrorCorrectionLevel::Level5) => Some(5),
+                    Some(ErrorCorrectionLevel::Level6) => Some(6),
+                    Some(ErrorCorrectionLevel::Level7) => Some(7),
+                    Some(ErrorCorrectionLevel::Level8) => Some(8),
+                    _ => None
+                };
+                if let Some(level) = level { self.symbol_fn(48, 69, &[48, 48 + level]); }
+                let truncated = matches!(symbol.symbol_type, SymbolType::PDF415Trunc);
+                self.symbol_fn(48, 70, &[truncated as u8]);
+                48
+            },
+            SymbolType::QRCode1 | SymbolType::QRCode2 => {
+                let model = if matches!(symbol.symbol_type, SymbolType::QRCode1) { 49 } else { 50 };
+                self.symbol_fn(49, 65, &[model, 0]);
+                if let Some(width) = symbol.width { self.symbol_fn(49, 67, &[width]); }
+                let level = match symbol.level {
+                    Some(ErrorCorrectionLevel::LevelL) => Some(48),
+                    Some(ErrorCorrectionLevel::LevelM) => Some(49),
+                    Some(ErrorCorrectionLevel::LevelQ) => Some(50),
+                    Some(ErrorCorrectionLevel::LevelH) => Some(51),
+                    _ => None
+                };
+                if let Some(level) = level { self.symbol_fn(49, 69, &[level]); }
+                49
+            },
+            SymbolType::MaxiCodeMode2 | SymbolType::MaxiCodeMode3 | SymbolType::MaxiCodeMode4
+                | SymbolType::MaxiCodeMode5 | SymbolType::MaxiCodeMode6 => {
+                let mode = match symbol.symbol_type {
+                    SymbolType::MaxiCodeMode2 => 50,
+                    SymbolType::MaxiCodeMode3 => 51,
+                    SymbolType::MaxiCodeMode5 => 53,
+                    SymbolType::MaxiCodeMode6 => 54,
+                    _ => 52
+                };
+                self.symbol_fn(50, 65, &[mode]);
+                50
+            },
+            SymbolType::GS1DatabarStacked | SymbolType::GS1DatabarStackedOmnidirectional | SymbolType::GS1DatabarExpandedStacked => {
+                if let Some(width) = symbol.width { self.symbol_fn(51, 67, &[width]); }
+                if let Some(size) = symbol.size { self.symbol_fn(51, 71, &[size, 0]); }
+                let m = match symbol.symbol_type {
+                    SymbolType::GS1DatabarStacked => 72,
+                    SymbolType::GS1DatabarStackedOmnidirectional => 73,
+                    _ => 76
+                };
+                let data = if m == 76 { gs1_data(&data)? } else { data.clone() };
+                self.align(symbol.align.as_ref());
+                let mut params = vec![48, m];
+                params.extend_from_slice(&data);
+                self.symbol_fn(51, 80, &params);
+                self.symbol_fn(51, 81, &[48]);
+                return Ok(());
+            },
+            SymbolType::AztecCodeFullRange | SymbolType::AztecCodeCompact => {
+                let compact = matches!(symbol.symbol_type, SymbolType::AztecCodeCompact);
+                self.symbol_fn(53, 66, &[compact as u8, 0]);
+                if let Some(width) = symbol.width { self.symbol_fn(53, 67, &[width]); }
+                if let Some(ErrorCorrectionLevel::Int(level)) = symbol.level { self.symbol_fn(53, 69, &[level.min(95) as u8]); }
+                53
+            },
+            SymbolType::DatamatrixSquare | SymbolType::DatamatrixRectangle8 | SymbolType::DatamatrixRectangle12
+                | SymbolType::DatamatrixRectangle16 => {
+                let shape: [u8; 3] = match symbol.symbol_type {
+                    SymbolType::DatamatrixRectangle8 => [1, 8, 0],
+                    SymbolType::DatamatrixRectangle12 => [1, 12, 0],
+                    SymbolType::DatamatrixRectangle16 => [1, 16, 0],
+                    _ => [0, 0, 0]
+                };
+                self.symbol_fn(54, 66, &shape);
+                if let Some(width) = symbol.width { self.symbol_fn(54, 67, &[width]); }
+                54
+            }
+        };
+
+        self.align(symbol.align.as_ref());
+        let mut params = vec![48];
+        params.extend_from_slice(&data);
+        self.symbol_fn(cn, 80, &params);
+        self.symbol_fn(cn, 81, &[48]);
+        Ok(())
+    }
+}
+
+/// Decode the `\xnn` and `\\` escapes allowed in barcode and symbol data. Other non-ASCII characters become `?`.
+fn unescape(text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(escaped) = rest.strip_prefix("\\\\") {
+            out.push(b'\\');
+            rest = escaped;
+            continue;
+        }
+        if let Some(byte) = rest.strip_prefix("\\x").and_then(|hex| hex.get(..2)).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            out.push(byte);
+            rest = &rest[4..];
+            continue;
+        }
+        out.push(if c.is_ascii() { c as u8 } else { b'?' });
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Convert GS1 data from the ePOS format: parentheses and spaces around application identifiers are only used for
+/// the human readable text, and `{(`, `{)` and `{*` stand for the literal characters. `{1` and `{{` are the same in ESC/POS.
+fn gs1_data(data: &[u8]) -> Result<Vec<u8>, EPOSError> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'{' => match bytes.next() {
+                Some(next @ (b'(' | b')' | b'*')) => out.push(*next),
+                Some(next) => out.extend_from_slice(&[b'{', *next]),
+                None => out.push(b'{')
+            },
+            b'(' | b')' | b' ' => {},
+            b'*' => return Err(EPOSError::UnsupportedError("automatic GS1 check digits")),
+            other => out.push(*other)
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use crate::{barcodes::{BarcodeType, ErrorCorrectionLevel, SymbolType}, error::EPOSError, formatters::{Align, CutType, Style},
+        normal::{Cut, Hline, NormalElement, VlineBegin}, status::{EposCode, PrinterStatus}, universal::{Barcode, Feed, Image, Symbol, Text}, Builder};
+
+    use super::{decode_status, encode, STATUS_QUERY};
+
+    #[test]
+    fn test_encode() {
+        let items: Vec<NormalElement> = vec![
+            Text { text: "Hi é\n".to_string(), double_width: Some(true), emph: Some(true), align: Some(Align::Center), ..Default::default() }.into(),
+            Text { text: "x".to_string(), height: Some(3), ..Default::default() }.into(),
+            Feed { line: Some(2), ..Default::default() }.into(),
+            Hline { x1: 0, x2: 9, style: Some(Style::ThinDouble) }.into(),
+            Cut { cut_type: CutType::Feed }.into(),
+        ];
+        assert_eq!(encode(None, &items).unwrap(), [
+            &[0x1b, b'@'][..],
+            &[0x1d, b'!', 0x10, 0x1b, b'E', 1, 0x1b, b'a', 1], b"Hi ?\n",
+            &[0x1d, b'!', 0x12], b"x",
+            &[0x1b, b'd', 2],
+            &[0x1d, b'v', b'0', 0, 2, 0, 3, 0, 0xff, 0xc0, 0, 0, 0xff, 0xc0],
+            &[0x1d, b'V', 66, 0],
+        ].concat());
+
+        let err = encode(None, &[VlineBegin { x: 1, style: None }.into()]).unwrap_err();
+        assert!(matches!(err, EPOSError::UnsupportedError("vertical lines")));
+    }
+
+    #[test]
+    fn test_encode_codes() {
+        let barcode = Barcode::builder(BarcodeType::GS1_128, "(01)04912345123459(10)A{(1{1").height(40).build().unwrap();
+        let qr = Symbol { text: "\\x41B".to_string(), symbol_type: SymbolType::QRCode2, level: Some(ErrorCorrectionLevel::LevelH), ..Default::default() };
+        let image = Image::from_raster(&[0x80, 0x40], 2, 2).unwrap();
+        let out = encode(None, &[barcode.into(), qr.into(), image.into()]).unwrap();
+        assert_eq!(out, [
+            &[0x1b, b'@'][..],
+            &[0x1d, b'h', 40, 0x1d, b'k', 74, 23], b"0104912345123459", b"10A(1{1",
+            &[0x1d, b'(', b'k', 4, 0, 49, 65, 50, 0],
+            &[0x1d, b'(', b'k', 3, 0, 49, 69, 51],
+            &[0x1d, b'(', b'k', 5, 0, 49, 80, 48, b'A', b'B'],
+            &[0x1d, b'(', b'k', 3, 0, 49, 81, 48],
+            &[0x1d, b'v', b'0', 0, 1, 0, 2, 0, 0x80, 0x40],
+        ].concat());
+
+
+        // alignment is set before the symbol is printed
+        let stacked = Symbol { text: "0401234567890".to_string(), symbol_type: SymbolType::GS1DatabarStacked, align: Some(Align::Center), ..Default::default() };
+        let out = encode(None, &[stacked.into()]).unwrap();
+        assert_eq!(out, [
+            &[0x1b, b'@', 0x1b, b'a', 1][..],
+            &[0x1d, b'(', b'k', 17, 0, 51, 80, 48, 72], b"0401234567890",
+            &[0x1d, b'(', b'k', 3, 0, 51, 81, 48],
+        ].concat());
+    }
+
+    #[test]
+    fn test_decode_status() {
+        let status = decode_status([0x1e, 0x16, 0x12, 0x1e]);
+        assert_eq!(status, PrinterStatus::OFFLINE | PrinterStatus::DRAWER_KICK | PrinterStatus::COVER_OPEN | PrinterStatus::RECEIPT_NEAR_END);
+        assert!(decode_status([0x12, 0x12, 0x12, 0x12]).is_empty());
+    }
+
+    /// A printer that records what it receives and answers status queries with `reply`.
+    async fn fake_printer(reply: [u8; 4]) -> (String, tokio::task::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            while !received.ends_with(&STATUS_QUERY) {
+                let mut buf = [0u8; 1024];
+                let read = stream.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..read]);
+            }
+            stream.write_all(&reply).await.unwrap();
+            received
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_print_over_tcp() {
+        let (url, printer) = fake_printer([0x12, 0x12, 0x12, 0x12]).await;
+        let mut handler = Builder::new(1000, "local_printer", url).unwrap().normal();
+        handler.add(Text { text: "hello\n".to_string(), ..Default::default() }).unwrap();
+        handler.print().await.unwrap();
+        assert_eq!(printer.await.unwrap(), [&[0x1b, b'@'][..], b"hello\n", &STATUS_QUERY].concat());
+
+        let (url, _printer) = fake_printer([0x1e, 0x16, 0x12, 0x12]).await;
+        let resp = Builder::new(1000, "local_printer", url).unwrap().status().await.unwrap();
+        assert!(!resp.success);
+        assert_eq!(resp.code, EposCode::CoverOpen);
+        assert!(resp.printer_status().contains(PrinterStatus::OFFLINE | PrinterStatus::DRAWER_KICK));
+
+        let (url, _printer) = fake_printer([0x1e, 0x16, 0x12, 0x12]).await;
+        let mut handler = Builder::new(1000, "local_printer", url).unwrap().normal();
+        handler.add(Text { text: "hello\n".to_string(), ..Default::default() }).unwrap();
+        let err = handler.print().await.unwrap_err();
+        assert!(matches!(err, EPOSError::ResponseError { status } if status.code == EposCode::CoverOpen));
+
+        let page = Builder::new(1000, "local_printer", "tcp://127.0.0.1:1").unwrap().page();
+        assert!(matches!(page.clone().print().await, Err(EPOSError::UnsupportedError("page mode"))));
+    }
+}
@@ -30,6 +30,24 @@ pub enum FeedPos {
 }
 
 
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+/// Paper type used by a layout
+pub enum PaperType {
+    #[serde(rename = "receipt")]
+    #[default]
+    /// Receipt paper without black marks
+    Receipt,
+    #[serde(rename = "receipt_bm")]
+    /// Receipt paper with black marks
+    ReceiptBm,
+    #[serde(rename = "label")]
+    /// Die-cut label paper
+    Label,
+    #[serde(rename = "label_bm")]
+    /// Label paper with black marks
+    LabelBm
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 /// Specifies the type of paper cut to perform
 pub enum CutType {
@@ -45,6 +63,104 @@ pub enum CutType {
 }
 
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Specifies the drawer kick-out connector pin used by a pulse
+pub enum Drawer {
+    #[serde(rename = "drawer_1")]
+    /// Pin 2 of the drawer kick-out connector
+    Drawer1,
+    #[serde(rename = "drawer_2")]
+    /// Pin 5 of the drawer kick-out connector
+    Drawer2
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Specifies the on/off time of a drawer kick pulse
+pub enum PulseTime {
+    #[serde(rename = "pulse_100")]
+    /// 100 msec on, 100 msec off
+    Pulse100,
+    #[serde(rename = "pulse_200")]
+    /// 200 msec on, 200 msec off
+    Pulse200,
+    #[serde(rename = "pulse_300")]
+    /// 300 msec on, 300 msec off
+    Pulse300,
+    #[serde(rename = "pulse_400")]
+    /// 400 msec on, 400 msec off
+    Pulse400,
+    #[serde(rename = "pulse_500")]
+    /// 500 msec on, 500 msec off
+    Pulse500
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Specifies the buzzer pattern of a sound
+pub enum SoundPattern {
+    #[serde(rename = "pattern_a")]
+    PatternA,
+    #[serde(rename = "pattern_b")]
+    PatternB,
+    #[serde(rename = "pattern_c")]
+    PatternC,
+    #[serde(rename = "pattern_d")]
+    PatternD,
+    #[serde(rename = "pattern_e")]
+    PatternE,
+    #[serde(rename = "error")]
+    /// Pattern used to signal an error
+    Error,
+    #[serde(rename = "receipt")]
+    /// Pattern used to signal a finished receipt
+    Receipt
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Character code tables that can be selected with `universal::Command::code_page`
+pub enum CodePage {
+    /// USA, standard Europe
+    PC437,
+    /// Katakana
+    Katakana,
+    /// Multilingual
+    PC850,
+    /// Portuguese
+    PC860,
+    /// Canadian-French
+    PC863,
+    /// Nordic
+    PC865,
+    /// Windows Latin-1
+    WPC1252,
+    /// Cyrillic #2
+    PC866,
+    /// Latin 2
+    PC852,
+    /// Euro
+    PC858,
+    /// Any other table, specified by its ESC/POS number
+    Other(u8)
+}
+
+impl CodePage {
+    /// The ESC/POS table number for the code page
+    pub fn number(&self) -> u8 {
+        match self {
+            CodePage::PC437 => 0,
+            CodePage::Katakana => 1,
+            CodePage::PC850 => 2,
+            CodePage::PC860 => 3,
+            CodePage::PC863 => 4,
+            CodePage::PC865 => 5,
+            CodePage::WPC1252 => 16,
+            CodePage::PC866 => 17,
+            CodePage::PC852 => 18,
+            CodePage::PC858 => 19,
+            CodePage::Other(num) => *num
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 /// Set the language used in the text line
 pub enum Lang {
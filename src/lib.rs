@@ -3,7 +3,7 @@
 //! Complete documentation for the ePOS API can be found here: <https://files.support.epson.com/pdf/pos/bulk/epos-print_xml_um_en_revi.pdf>.
 //! 
 //! epos-rs handles all API details, providing an object-based interface for creating receipt objects, and a `print` method for handling the underlying XML and network request.
-//! ```rust
+//! ```rust,no_run
 //! # tokio_test::block_on(async {
 //! use epos_rs::universal::{Symbol, Text};
 //! use epos_rs::normal::Cut;
@@ -28,19 +28,28 @@
 //! # })
 //! ```
 
-use std::fmt::Display;
+use std::{fmt::Display, pin::Pin, sync::Arc, time::Duration};
+use log::debug;
+use formatters::FeedPos;
+use futures_util::{stream, Stream};
 
-use error::EPOSError;
-use normal::NormalItem;
-use page::PageItem;
-use quick_xml::DeError;
+use error::{EPOSError, ValidationError};
+use normal::{NormalElement, NormalItem, Pulse, Recovery, Reset};
+use page::{PageElement, PageItem};
+use parse::{Document, UnknownElements};
+use retry::RetryPolicy;
 use reqwest::IntoUrl;
-use soap::{EnumBody, PageWrapper, ENDPOINT};
-use url::Url; 
+use escpos::EscPosTransport;
+use status::{JobStatus, PrintReceipt, PrinterStatus, StatusChange, StatusEvent};
+use transport::{EnumBody, HttpConfig, HttpTransport, Request, Transport};
+use universal::{Feed, Layout};
 
 
 mod soap;
+pub mod escpos;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod barcodes;
 pub mod formatters;
 pub mod error;
@@ -48,11 +57,19 @@ pub mod status;
 pub mod page;
 pub mod normal;
 pub mod universal;
+pub mod raster;
+pub mod preview;
+pub mod parse;
+pub mod transport;
+pub mod retry;
+pub mod spool;
+pub mod pool;
 
 /// Builder manages the connection to the printer.
 #[derive(Clone, Debug)]
 pub struct Builder {
-    endpoint: Url,
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy,
     dev_id: String,
     timeout: i32,
 }
@@ -62,39 +79,207 @@ impl Builder {
     /// Create a new printer connection. To use this connection to print, call either `builder.page()` or `builder.normal()`.
     /// On most printers, the default device ID is `"local_printer"`.
     /// The `timeout` is not a network timeout, but serves as a device-side parser timeout. On most systems, a reasonable timeout is ~10000.
+    ///
+    /// An endpoint such as `"tcp://192.168.1.50:9100"` selects the raw ESC/POS backend instead of ePOS-Print; see `escpos` for what it supports.
+    /// With that backend, the `timeout` is also used as the network timeout, and the device ID is ignored.
     pub fn new<U: IntoUrl>(timeout: i32, dev_id: &str, endpoint: U) -> Result<Builder, EPOSError> {
-        Ok( Builder{
-            timeout: timeout,
+        let endpoint = endpoint.into_url()?;
+        if endpoint.scheme() == escpos::SCHEME {
+            return Ok(Builder::with_transport(timeout, dev_id, EscPosTransport::new(endpoint)?))
+        }
+        Ok(Builder::with_transport(timeout, dev_id, HttpTransport::new(endpoint)?))
+    }
+
+    /// Create a printer connection to an ePOS-Print endpoint, with a client built from the given options.
+    /// The client is shared by every handler created from the builder.
+    pub fn with_http_config<U: IntoUrl>(timeout: i32, dev_id: &str, endpoint: U, config: &HttpConfig) -> Result<Builder, EPOSError> {
+        Ok(Builder::with_transport(timeout, dev_id, HttpTransport::with_config(endpoint, config)?))
+    }
+
+    /// Create a printer connection that sends requests through the given transport. See `transport` for the transports available.
+    pub fn with_transport<T: Transport + 'static>(timeout: i32, dev_id: &str, transport: T) -> Builder {
+        Builder{
+            timeout,
             dev_id: dev_id.to_string(),
-            endpoint: endpoint.into_url()?.join(ENDPOINT)?
-        })
+            transport: Arc::new(transport),
+            retry: RetryPolicy::none()
+        }
     }
 
-    /// Return a status object from the printer
+    /// Set how failed requests are retried, for this builder and the handlers created from it afterwards.
+    /// By default, each request is sent once. See `retry` for which failures are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// Return a status object from the printer.
+    /// Use `printer_status()` and `battery_status()` on the response to decode the status and battery fields.
+    /// Failed queries are retried after any network error, as allowed by the retry policy.
     pub async fn status(&self) -> Result<status::Response, EPOSError>{
-        let final_body = EnumBody::Emtpy {  };
-        let resp = soap::send_raw(final_body, &self.dev_id, self.timeout, &self.endpoint).await?;
+        self.query(None).await
+    }
+
+    /// Ask the printer what became of the document printed with the given job ID,
+    /// such as to find out whether a print that timed out was printed after all.
+    pub async fn job_status(&self, job_id: &str) -> Result<JobStatus, EPOSError> {
+        check_job_id(job_id)?;
+        Ok(self.query(Some(job_id)).await?.into())
+    }
+
+    /// Poll the printer's status every `interval`, and report each change.
+    ///
+    /// The first item is the status when the stream is first polled, later items only come when the status changes.
+    /// Changes to `PrinterStatus::PRINT_SUCCESS` alone aren't reported, since it changes with every print.
+    /// Changes to `PrinterStatus::DRAWER_KICK`, the drawer sensor or `BATTERY_OFFLINE` on mobile printers, are reported without an event.
+    /// Intervals shorter than 100 ms are treated as 100 ms.
+    /// While the printer can't be reached, the wait between polls doubles, up to a minute or `interval` if that is longer.
+    /// The stream never ends; drop it to stop polling.
+    ///
+    /// ```rust,no_run
+    /// # tokio_test::block_on(async {
+    /// use std::time::Duration;
+    /// use futures_util::StreamExt;
+    /// use epos_rs::{status::StatusEvent, Builder};
+    ///
+    /// let builder = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap();
+    /// let mut changes = builder.watch_status(Duration::from_secs(5));
+    /// while let Some(change) = changes.next().await {
+    ///     if change.events.contains(&StatusEvent::PaperOut) {
+    ///         println!("out of paper");
+    ///     }
+    /// }
+    /// # })
+    /// ```
+    pub fn watch_status(&self, interval: Duration) -> Pin<Box<dyn Stream<Item = StatusChange> + Send>> {
+        let interval = interval.max(Duration::from_millis(100));
+        let backoff = RetryPolicy::new().initial_backoff(interval).max_backoff(interval.max(Duration::from_secs(60)));
+        // the last status reported, `None` before the first poll
+        let state: (Builder, Option<Option<PrinterStatus>>, u32) = (self.clone(), None, 0);
+        Box::pin(stream::unfold(state, move |(builder, mut last, mut failures)| {
+            let backoff = backoff.clone();
+            async move {
+                loop {
+                    if last.is_some() {
+                        tokio::time::sleep(if failures == 0 { interval } else { backoff.backoff(failures + 1) }).await;
+                    }
+                    let status = match builder.status().await {
+                        Ok(resp) => {
+                            failures = 0;
+                            Some(resp.printer_status())
+                        },
+                        Err(err) => {
+                            debug!("status poll failed: {}", err);
+                            failures = failures.saturating_add(1);
+                            None
+                        }
+                    };
+                    let previous = match last {
+                        Some(previous) if previous.map(|bits| bits - PrinterStatus::PRINT_SUCCESS) == status.map(|bits| bits - PrinterStatus::PRINT_SUCCESS) => continue,
+                        Some(previous) => previous,
+                        None => Some(PrinterStatus::empty())
+                    };
+                    last = Some(status);
+                    let change = StatusChange { status, events: StatusEvent::diff(previous, status) };
+                    return Some((change, (builder, last, failures)));
+                }
+            }
+        }))
+    }
 
-        Ok(resp.body.response)
+    /// Send a request without a document, retrying after network errors.
+    async fn query(&self, job_id: Option<&str>) -> Result<status::Response, EPOSError> {
+        let request = Request { body: EnumBody::Empty, dev_id: &self.dev_id, timeout: self.timeout, job_id };
+        let mut attempt = 1;
+        loop {
+            match self.transport.send(request).await {
+                Err(err) if attempt < self.retry.attempts() && self.retry.retries_query(&err) => {
+                    debug!("status query failed, retrying: {}", err);
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                    attempt += 1;
+                },
+                result => return result
+            }
+        }
+    }
+
+    /// Open the cash drawer by sending a single default pulse to drawer kick-out connector pin 2.
+    /// Returns the state of `PrinterStatus::DRAWER_KICK` reported after the pulse.
+    /// On most drawers, `true` means the drawer is open.
+    pub async fn open_drawer(&self) -> Result<bool, EPOSError> {
+        let resp = self.send_single(Pulse::default().into()).await?;
+        let status: PrinterStatus = resp.status.into();
+
+        Ok(status.contains(PrinterStatus::DRAWER_KICK))
+    }
+
+    /// Recover the printer from a recoverable error, such as when `PrinterStatus::AUTO_RECOVERABLE_ERROR` or `PrinterStatus::AUTOCUTTER_ERROR` is set.
+    pub async fn recover(&self) -> Result<status::Response, EPOSError> {
+        self.send_single(Recovery{}.into()).await
+    }
+
+    /// Reset the printer.
+    pub async fn reset(&self) -> Result<status::Response, EPOSError> {
+        self.send_single(Reset{}.into()).await
+    }
+
+    /// Send a document made of a single element.
+    async fn send_single(&self, element: NormalElement) -> Result<status::Response, EPOSError> {
+        let request = Request { body: EnumBody::NoPage { layout: None, body: &[element] }, dev_id: &self.dev_id, timeout: self.timeout, job_id: None };
+        send(self.transport.as_ref(), &self.retry, request).await
     }
 
     /// create a new builder object for writing in page mode (a formatted area of a set size).
     pub fn page(&self) -> PageBuilder {
         PageBuilder{
             build: Vec::new(),
+            layout: None,
+            job_id: None,
             timeout: self.timeout,
             dev_id: self.dev_id.clone(),
-            endpoint: self.endpoint.clone()
+            transport: self.transport.clone(),
+            retry: self.retry.clone()
+        }
+    }
+
+    /// Load a stored normal mode document, either a SOAP envelope or a bare `epos-print` element, into a new builder.
+    /// Items are checked as if they were added with `NormalBuilder::add`. See `parse::parse` for how unknown elements are handled.
+    pub fn load_normal(&self, xml: &str, unknown: UnknownElements) -> Result<NormalBuilder, EPOSError> {
+        let Document::Normal { layout, items } = parse::parse(xml, unknown)? else {
+            return Err(EPOSError::DocumentError { position: 0, reason: "expected a normal mode document, found a page".to_string() });
+        };
+        let mut builder = self.normal();
+        builder.layout = layout;
+        for item in items {
+            builder.push(item)?;
         }
+        Ok(builder)
+    }
+
+    /// Load a stored page mode document, either a SOAP envelope or a bare `epos-print` element, into a new builder.
+    /// Items are checked as if they were added with `PageBuilder::add`. See `parse::parse` for how unknown elements are handled.
+    pub fn load_page(&self, xml: &str, unknown: UnknownElements) -> Result<PageBuilder, EPOSError> {
+        let Document::Page { layout, items } = parse::parse(xml, unknown)? else {
+            return Err(EPOSError::DocumentError { position: 0, reason: "expected a page mode document".to_string() });
+        };
+        let mut builder = self.page();
+        builder.layout = layout;
+        for item in items {
+            builder.push(item)?;
+        }
+        Ok(builder)
     }
 
     /// create a new builder object for writing in normal mode (commands are printed one line at a time).
     pub fn normal(&self) -> NormalBuilder {
         NormalBuilder {
             build: Vec::new(),
+            open_vlines: Vec::new(),
+            layout: None,
+            job_id: None,
             timeout: self.timeout,
             dev_id: self.dev_id.clone(),
-            endpoint: self.endpoint.clone()
+            transport: self.transport.clone(),
+            retry: self.retry.clone()
         }
     }
 }
@@ -102,85 +287,251 @@ impl Builder {
 /// manage and track a print job in page mode.
  #[derive(Clone, Debug)]
 pub struct PageBuilder {
-    build: Vec<String>,
+    build: Vec<PageElement>,
+    layout: Option<Layout>,
+    job_id: Option<String>,
     timeout: i32,
     dev_id: String,
-    endpoint: Url
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy
 }
 
 impl Display for PageBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.build)
+        for item in &self.build {
+            writeln!(f, "{}", item.to_xml().map_err(|_| std::fmt::Error)?)?;
+        }
+        Ok(())
     }
 }
 
 impl PageBuilder {
 
     /// Add a command to the page
-    pub fn add <I: PageItem> (&mut self, item: I) -> Result<(), DeError> {
-        let output = quick_xml::se::to_string(&item)?;
-        self.build.push(output);
+    /// Returns an error if the command fails validation, such as a barcode with data its type cannot encode
+    /// or a symbol with parameters its type does not support.
+    pub fn add <I: PageItem> (&mut self, item: I) -> Result<(), EPOSError> {
+        self.push(item.into())
+    }
+
+    fn push(&mut self, item: PageElement) -> Result<(), EPOSError> {
+        match &item {
+            PageElement::Barcode(barcode) => barcode.validate()?,
+            PageElement::Symbol(symbol) => symbol.validate()?,
+            PageElement::Sound(sound) => sound.validate()?,
+            _ => {}
+        }
+        self.build.push(item);
         Ok(())
     }
 
-    /// print a page
-    pub async fn print(&mut self) -> Result<(), EPOSError> {
-        let final_body = EnumBody::Page { body: PageWrapper{body: self.build.join("\n")}}; 
-        soap::send(final_body, &self.dev_id, self.timeout, &self.endpoint).await?;
+    /// Set the paper layout. The layout is sent ahead of the page.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = Some(layout);
+    }
 
+    /// Set the print job ID sent with the document, so `Builder::job_status` can tell whether it was printed.
+    /// IDs are 1 to 30 letters, digits, `_`, `.` or `-`, and should be unique, such as an order number.
+    /// With a job ID, a print that failed without knowing whether the printer received it is checked with the printer,
+    /// and is only sent again if the printer has no record of it, as allowed by the retry policy.
+    pub fn set_job_id(&mut self, job_id: impl Into<String>) -> Result<(), ValidationError> {
+        let job_id = job_id.into();
+        check_job_id(&job_id)?;
+        self.job_id = Some(job_id);
         Ok(())
     }
+
+    /// The commands added so far, in order.
+    pub fn items(&self) -> &[PageElement] {
+        &self.build
+    }
+
+    /// Check the parts of the page that can only be checked once it's complete.
+    fn check(&self) -> Result<(), ValidationError> {
+        let feeds_to_next_tof = self.build.iter().any(|item| matches!(item, PageElement::Feed(feed) if is_next_tof(feed)));
+        check_next_tof(feeds_to_next_tof, &self.layout)
+    }
+
+    /// print a page.
+    /// Returns an error without sending anything if the page feeds to `FeedPos::NextTof` without a label layout.
+    pub async fn print(&mut self) -> Result<PrintReceipt, EPOSError> {
+        self.check()?;
+        let final_body = EnumBody::Page { layout: self.layout.as_ref(), body: &self.build }; 
+        let request = Request { body: final_body, dev_id: &self.dev_id, timeout: self.timeout, job_id: self.job_id.as_deref() };
+        let response = send(self.transport.as_ref(), &self.retry, request).await?;
+
+        Ok(PrintReceipt { job_id: self.job_id.clone(), response })
+    }
 }
 
 /// Manage and track a print job in normal mode
+#[derive(Clone, Debug)]
 pub struct NormalBuilder {
-    build: Vec<String>,
+    build: Vec<NormalElement>,
+    /// x positions of vertical lines that have been started but not yet ended
+    open_vlines: Vec<u16>,
+    layout: Option<Layout>,
+    job_id: Option<String>,
     timeout: i32,
     dev_id: String,
-    endpoint: Url
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy
 }
 
 impl Display for NormalBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.build)
+        for item in &self.build {
+            writeln!(f, "{}", item.to_xml().map_err(|_| std::fmt::Error)?)?;
+        }
+        Ok(())
     }
 }
 
 impl NormalBuilder {
-    /// Add a command
-    pub fn  add <I: NormalItem> (&mut self, item: I) -> Result<(), DeError> {
-        let output = quick_xml::se::to_string(&item)?;
-        self.build.push(output);
+    /// Add a command.
+    /// Returns an error if the command fails validation, such as a barcode with data its type cannot encode
+    /// or a symbol with parameters its type does not support,
+    /// or if it ends a vertical line that was never started.
+    pub fn  add <I: NormalItem> (&mut self, item: I) -> Result<(), EPOSError> {
+        self.push(item.into())
+    }
+
+    fn push(&mut self, item: NormalElement) -> Result<(), EPOSError> {
+        match &item {
+            NormalElement::Barcode(barcode) => barcode.validate()?,
+            NormalElement::Symbol(symbol) => symbol.validate()?,
+            NormalElement::Sound(sound) => sound.validate()?,
+            NormalElement::VlineBegin(begin) => self.open_vlines.push(begin.x),
+            NormalElement::VlineEnd(end) => {
+                match self.open_vlines.iter().position(|x| *x == end.x) {
+                    Some(idx) => { self.open_vlines.remove(idx); },
+                    None => return Err(ValidationError::UnmatchedVlineEnd { x: end.x }.into())
+                }
+            },
+            _ => {}
+        }
+
+        self.build.push(item);
         Ok(())
     }
 
-    /// print the document
-    pub async fn print(&mut self) -> Result<(), EPOSError> {
-        let final_body = EnumBody::NoPage { body:  self.build.join("\n")}; 
-        soap::send(final_body, &self.dev_id, self.timeout, &self.endpoint).await?;
+    /// Set the paper layout. The layout is sent ahead of the document body.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = Some(layout);
+    }
 
+    /// Set the print job ID sent with the document, so `Builder::job_status` can tell whether it was printed.
+    /// IDs are 1 to 30 letters, digits, `_`, `.` or `-`, and should be unique, such as an order number.
+    /// With a job ID, a print that failed without knowing whether the printer received it is checked with the printer,
+    /// and is only sent again if the printer has no record of it, as allowed by the retry policy.
+    pub fn set_job_id(&mut self, job_id: impl Into<String>) -> Result<(), ValidationError> {
+        let job_id = job_id.into();
+        check_job_id(&job_id)?;
+        self.job_id = Some(job_id);
         Ok(())
     }
+
+    /// The commands added so far, in order.
+    pub fn items(&self) -> &[NormalElement] {
+        &self.build
+    }
+
+    /// Check the parts of the document that can only be checked once it's complete.
+    fn check(&self) -> Result<(), ValidationError> {
+        if let Some(x) = self.open_vlines.first() {
+            return Err(ValidationError::UnclosedVline { x: *x })
+        }
+        let feeds_to_next_tof = self.build.iter().any(|item| matches!(item, NormalElement::Feed(feed) if is_next_tof(feed)));
+        check_next_tof(feeds_to_next_tof, &self.layout)
+    }
+
+    /// print the document.
+    /// Returns an error without sending anything if a vertical line was started but never ended,
+    /// or if the document feeds to `FeedPos::NextTof` without a label layout.
+    pub async fn print(&mut self) -> Result<PrintReceipt, EPOSError> {
+        self.check()?;
+        let final_body = EnumBody::NoPage { layout: self.layout.as_ref(), body: &self.build }; 
+        let request = Request { body: final_body, dev_id: &self.dev_id, timeout: self.timeout, job_id: self.job_id.as_deref() };
+        let response = send(self.transport.as_ref(), &self.retry, request).await?;
+
+        Ok(PrintReceipt { job_id: self.job_id.clone(), response })
+    }
+}
+
+/// Send a document, returning an error if the printer reports that it failed.
+/// Failures that the retry policy allows are retried, after waiting for its backoff.
+/// If it's unknown whether the printer received a document with a job ID, the job status is checked before sending it again.
+async fn send(transport: &dyn Transport, retry: &RetryPolicy, request: Request<'_>) -> Result<status::Response, EPOSError> {
+    let mut attempt = 1;
+    loop {
+        let result = match transport.send(request).await {
+            Ok(resp) if !resp.success => Err(EPOSError::ResponseError { status: resp }),
+            result => result
+        };
+        match result {
+            Err(err) if attempt < retry.attempts() && retry.retries(&err) => {
+                debug!("print failed, retrying: {}", err);
+            },
+            Err(err) if attempt < retry.attempts() && request.job_id.is_some() && retry.retries_query(&err) => {
+                debug!("print failed, checking job status: {}", err);
+                match transport.send(Request { body: EnumBody::Empty, ..request }).await.map(JobStatus::from) {
+                    Ok(JobStatus::Printed(resp)) => return Ok(resp),
+                    Ok(JobStatus::NotFound(_)) => {},
+                    _ => return Err(err)
+                }
+            },
+            result => return result
+        }
+        tokio::time::sleep(retry.backoff(attempt)).await;
+        attempt += 1;
+    }
+}
+
+/// Print job IDs are 1 to 30 characters from `A-Z`, `a-z`, `0-9`, `_`, `.` and `-`.
+fn check_job_id(job_id: &str) -> Result<(), ValidationError> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-');
+    if job_id.is_empty() || job_id.len() > 30 || !job_id.chars().all(valid_char) {
+        return Err(ValidationError::InvalidJobId(job_id.to_string()))
+    }
+    Ok(())
 }
 
+/// Returns true if the feed is to the head of the next label.
+fn is_next_tof(feed: &Feed) -> bool {
+    matches!(feed.pos, Some(FeedPos::NextTof))
+}
+
+/// Feeds to the next label only make sense if a label layout has been set.
+fn check_next_tof(feeds_to_next_tof: bool, layout: &Option<Layout>) -> Result<(), ValidationError> {
+    if feeds_to_next_tof && !layout.as_ref().is_some_and(Layout::is_label) {
+        return Err(ValidationError::NextTofWithoutLabel)
+    }
+    Ok(())
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::{barcodes::SymbolType, formatters::Align, normal::{Cut, Hline}, page, universal::{Text, Symbol, Feed}, Builder};
+    use crate::{barcodes::SymbolType, error::{EPOSError, ValidationError}, formatters::{Align, FeedPos, PaperType}, normal::{Cut, Hline, VlineBegin, VlineEnd}, page, parse::UnknownElements, status::{ChargeLevel, EposCode, JobStatus, PrinterStatus, Response, StatusEvent}, testing::{MockPrinter, MockResponse}, transport::RecordingTransport, universal::{Barcode, Text, Symbol, Sound, Feed, Layout}, Builder};
 
 
     #[tokio::test]
     async fn test_status_soap() {
-        let handler = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap();
+        let printer = MockPrinter::start().await.unwrap();
+        printer.push_response(MockResponse::success().with_status(251658262).with_battery(0x3133));
+        let handler = Builder::new(10000, "local_printer", printer.url()).unwrap();
         let resp = handler.status().await.unwrap();
         println!("got: {}", resp);
         println!("raw: {:?}", resp);
+        assert!(resp.success);
+        assert_eq!(resp.status, 251658262);
+        assert_eq!(resp.battery_status().unwrap().level, ChargeLevel::Medium);
+        assert_eq!(printer.requests()[0].body, "");
     }
 
     #[tokio::test]
     async fn test_normal() {
-
-        let mut handler = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap().normal();
+        let printer = MockPrinter::start().await.unwrap();
+        let mut handler = Builder::new(10000, "local_printer", printer.url()).unwrap().normal();
 
         handler.add(Text{text: String::from("I HATE XML\n\n"), double_height: Some(true), 
         double_width: Some(true), align: Some(Align::Center), ..Default::default()}).unwrap();
@@ -196,6 +547,142 @@ mod tests {
         handler.add(Cut{cut_type: crate::formatters::CutType::Feed}).unwrap();
 
         handler.print().await.unwrap();
+
+        let requests = printer.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].dev_id, "local_printer");
+        assert_eq!(requests[0].body, concat!(
+            r#"<text dw="true" dh="true" align="center">I HATE XML"#, "\n\n</text>",
+            r#"<text dw="false" dh="false" align="center">Writing drivers"#, "\n for\n recept printers\n sucks\n</text>",
+            r#"<hline x1="100" x2="200" style="thin_double"/>"#,
+            r#"<hline x1="400" x2="500" style="thin_double"/>"#,
+            r#"<symbol type="maxicode_mode_4">HELP ME</symbol>"#,
+            r#"<feed line="5"/>"#,
+            r#"<cut type="feed"/>"#));
+    }
+
+    #[tokio::test]
+    async fn test_print_error() {
+        let printer = MockPrinter::start().await.unwrap();
+        printer.push_response(MockResponse::error("EPTR_COVER_OPEN").with_status(0x00000028));
+        let mut handler = Builder::new(10000, "local_printer", printer.url()).unwrap().normal();
+        handler.add(Text{text: String::from("hello\n"), ..Default::default()}).unwrap();
+
+        let err = handler.print().await.unwrap_err();
+        assert!(matches!(err, EPOSError::ResponseError { status } if status.code == EposCode::CoverOpen));
+    }
+
+    #[tokio::test]
+    async fn test_job_id() {
+        let printer = MockPrinter::start().await.unwrap();
+        let builder = Builder::new(10000, "local_printer", printer.url()).unwrap();
+        let mut handler = builder.normal();
+        handler.add(Text{text: String::from("order 1042\n"), ..Default::default()}).unwrap();
+        assert_eq!(handler.set_job_id("order 1042"), Err(ValidationError::InvalidJobId("order 1042".to_string())));
+        assert!(handler.set_job_id("a".repeat(31)).is_err());
+        handler.set_job_id("order-1042").unwrap();
+
+        let receipt = handler.print().await.unwrap();
+        assert_eq!(receipt.job_id.as_deref(), Some("order-1042"));
+        assert_eq!(receipt.response.print_job_id.as_deref(), Some("order-1042"));
+
+        printer.push_response(MockResponse::error("Printing"));
+        printer.push_response(MockResponse::error("JobNotFound"));
+        printer.push_response(MockResponse::error("EPTR_COVER_OPEN"));
+        assert!(matches!(builder.job_status("order-1042").await.unwrap(), JobStatus::Printing(_)));
+        assert!(matches!(builder.job_status("order-1042").await.unwrap(), JobStatus::NotFound(_)));
+        assert!(matches!(builder.job_status("order-1042").await.unwrap(), JobStatus::Failed(resp) if resp.code == EposCode::CoverOpen));
+        assert!(matches!(builder.job_status("order-1042").await.unwrap(), JobStatus::Printed(_)));
+        assert!(builder.job_status("").await.is_err());
+
+        let requests = printer.requests();
+        assert_eq!(requests.len(), 5);
+        assert!(requests.iter().all(|req| req.print_job_id.as_deref() == Some("order-1042")));
+        assert_eq!(requests[0].body, "<text>order 1042\n</text>");
+        assert_eq!(requests[1].body, "");
+    }
+
+    #[tokio::test]
+    async fn test_watch_status() {
+        use futures_util::StreamExt;
+
+        let recorder = RecordingTransport::new();
+        let ready = PrinterStatus::RESERVED_FIXED | PrinterStatus::PRINT_SUCCESS;
+        // the print success bit changes with every print, so it alone isn't reported, but the drawer sensor is
+        for status in [ready, ready, PrinterStatus::RESERVED_FIXED, ready | PrinterStatus::DRAWER_KICK, ready | PrinterStatus::COVER_OPEN] {
+            recorder.push_response(Response { success: true, status: status.bits(), ..Default::default() });
+        }
+        recorder.push_error(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into());
+        recorder.push_error(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into());
+        let builder = Builder::with_transport(10000, "local_printer", recorder.clone());
+        // too short an interval is raised to 100 ms
+        let start = std::time::Instant::now();
+        let changes: Vec<_> = builder.watch_status(std::time::Duration::ZERO).take(5).collect().await;
+        assert!(start.elapsed() >= std::time::Duration::from_millis(600));
+
+        assert_eq!(changes.iter().map(|change| change.status).collect::<Vec<_>>(),
+            [Some(ready), Some(ready | PrinterStatus::DRAWER_KICK), Some(ready | PrinterStatus::COVER_OPEN), None, Some(ready)]);
+        assert_eq!(changes[0].events, []);
+        assert_eq!(changes[1].events, []);
+        assert_eq!(changes[2].events, [StatusEvent::CoverOpened]);
+        assert_eq!(changes[3].events, [StatusEvent::Unreachable]);
+        assert_eq!(changes[4].events, [StatusEvent::Reachable, StatusEvent::Recovered]);
+        assert_eq!(recorder.requests().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_unbalanced_vline() {
+        let mut handler = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap().normal();
+        let err = handler.add(VlineEnd{x: 100, style: None}).unwrap_err();
+        assert!(matches!(err, EPOSError::ValidationError(ValidationError::UnmatchedVlineEnd { x: 100 })));
+
+        handler.add(VlineBegin{x: 100, style: None}).unwrap();
+        handler.add(VlineBegin{x: 200, style: None}).unwrap();
+        handler.add(VlineEnd{x: 100, style: None}).unwrap();
+        let err = handler.print().await.unwrap_err();
+        assert!(matches!(err, EPOSError::ValidationError(ValidationError::UnclosedVline { x: 200 })));
+    }
+
+    #[tokio::test]
+    async fn test_next_tof_without_label() {
+        let mut handler = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap().normal();
+        handler.add(Feed{pos: Some(FeedPos::NextTof), ..Default::default()}).unwrap();
+        handler.set_layout(Layout{paper_type: PaperType::ReceiptBm, ..Default::default()});
+        let err = handler.print().await.unwrap_err();
+        assert!(matches!(err, EPOSError::ValidationError(ValidationError::NextTofWithoutLabel)));
+    }
+
+    #[tokio::test]
+    async fn test_load_and_reprint() {
+        let printer = MockPrinter::start().await.unwrap();
+        let builder = Builder::new(10000, "local_printer", printer.url()).unwrap();
+        let mut handler = builder.normal();
+        handler.add(Text{text: String::from(" Fish & Chips\n"), ..Default::default()}).unwrap();
+        handler.add(VlineBegin{x: 100, style: None}).unwrap();
+        handler.add(VlineEnd{x: 100, style: None}).unwrap();
+        handler.print().await.unwrap();
+
+        let archived = printer.requests()[0].envelope.clone();
+        let mut reloaded = builder.load_normal(&archived, UnknownElements::Reject).unwrap();
+        assert_eq!(reloaded.items().len(), 3);
+        reloaded.print().await.unwrap();
+        assert_eq!(printer.requests()[1].envelope, archived);
+
+        let err = builder.load_normal(r#"<epos-print><vline-end x="5"/></epos-print>"#, UnknownElements::Reject).unwrap_err();
+        assert!(matches!(err, EPOSError::ValidationError(ValidationError::UnmatchedVlineEnd { x: 5 })));
+        assert!(builder.load_page(&archived, UnknownElements::Reject).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_barcode() {
+        let mut handler = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap().page();
+        let err = handler.add(Barcode::default()).unwrap_err();
+        assert!(matches!(err, EPOSError::ValidationError(ValidationError::InvalidLength { barcode: "upc_a", actual: 0, .. })));
+        assert!(handler.items().is_empty());
+
+        let mut handler = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap().normal();
+        let err = handler.add(Sound { cycle: Some(500), ..Default::default() }).unwrap_err();
+        assert!(matches!(err, EPOSError::ValidationError(ValidationError::OutOfRange { field: "sound cycle", value: 500, .. })));
     }
 
     #[tokio::test]
@@ -209,13 +696,20 @@ mod tests {
 
         let feed = Feed { unit: None, line: Some(200), linespc: None, pos: None };
 
-        let mut handler = Builder::new(10000, "local_printer",  "http://192.168.1.194").unwrap().page();
+        let printer = MockPrinter::start().await.unwrap();
+        let mut handler = Builder::new(10000, "local_printer",  printer.url()).unwrap().page();
         handler.add(area).unwrap();
         handler.add(ex1).unwrap();
         handler.add(rect).unwrap();
         handler.add(feed).unwrap();
 
         handler.print().await.unwrap();
+
+        assert_eq!(printer.requests()[0].body, concat!(
+            r#"<page><area x="0" y="0" width="500" height="500"/>"#,
+            r#"<text dw="false" dh="false" align="center">"#, "\nI HATE XML\n\n</text>",
+            r#"<rectangle x1="0" y1="0" x2="200" y2="100"/>"#,
+            r#"<feed line="200"/></page>"#));
     }
 
 
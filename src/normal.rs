@@ -1,11 +1,70 @@
 //! Types that are exclusive to normal mode.
+use quick_xml::DeError;
 use serde::{Deserialize, Serialize};
 
-use crate::formatters::{CutType, Style};
+use crate::{formatters::{CutType, Drawer, PulseTime, Style}, universal::{Barcode, Command, Feed, Image, Raw, Sound, Symbol, Text}};
 
-pub trait NormalItem: Serialize {}
+pub trait NormalItem: Serialize + Into<NormalElement> {}
 
-#[derive(Deserialize, Serialize, Debug)]
+/// A single command in a normal mode document.
+#[derive(Debug, Clone)]
+pub enum NormalElement {
+    Text(Text),
+    Feed(Feed),
+    Barcode(Barcode),
+    Symbol(Symbol),
+    Image(Image),
+    Sound(Sound),
+    Command(Command),
+    Cut(Cut),
+    Hline(Hline),
+    VlineBegin(VlineBegin),
+    VlineEnd(VlineEnd),
+    Pulse(Pulse),
+    Recovery(Recovery),
+    Reset(Reset),
+    /// An element passed through unchanged
+    Raw(Raw)
+}
+
+impl NormalElement {
+    /// Serialize the element to its ePOS XML representation.
+    pub fn to_xml(&self) -> Result<String, DeError> {
+        match self {
+            NormalElement::Text(item) => quick_xml::se::to_string(item),
+            NormalElement::Feed(item) => quick_xml::se::to_string(item),
+            NormalElement::Barcode(item) => quick_xml::se::to_string(item),
+            NormalElement::Symbol(item) => quick_xml::se::to_string(item),
+            NormalElement::Image(item) => quick_xml::se::to_string(item),
+            NormalElement::Sound(item) => quick_xml::se::to_string(item),
+            NormalElement::Command(item) => quick_xml::se::to_string(item),
+            NormalElement::Cut(item) => quick_xml::se::to_string(item),
+            NormalElement::Hline(item) => quick_xml::se::to_string(item),
+            NormalElement::VlineBegin(item) => quick_xml::se::to_string(item),
+            NormalElement::VlineEnd(item) => quick_xml::se::to_string(item),
+            NormalElement::Pulse(item) => quick_xml::se::to_string(item),
+            NormalElement::Recovery(item) => quick_xml::se::to_string(item),
+            NormalElement::Reset(item) => quick_xml::se::to_string(item),
+            NormalElement::Raw(item) => Ok(item.xml().to_string()),
+        }
+    }
+}
+
+macro_rules! normal_element_from {
+    ($($item:ident),*) => {
+        $(
+            impl From<$item> for NormalElement {
+                fn from(item: $item) -> Self {
+                    NormalElement::$item(item)
+                }
+            }
+        )*
+    };
+}
+
+normal_element_from!(Text, Feed, Barcode, Symbol, Image, Sound, Command, Cut, Hline, VlineBegin, VlineEnd, Pulse, Recovery, Reset, Raw);
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename ="cut")]
 /// Cut the paper.
 pub struct  Cut {
@@ -16,7 +75,7 @@ pub struct  Cut {
 impl NormalItem for Cut {}
 
 /// Draw a horizontal line
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename ="hline")]
 pub struct Hline {
     /// Specifies the horizontal draw start position in units of dots.
@@ -31,12 +90,66 @@ pub struct Hline {
 
 impl NormalItem for Hline {}
 
+/// Start drawing a vertical line. Every `VlineBegin` must be closed by a `VlineEnd` at the same `x` position.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename ="vline-begin")]
+pub struct VlineBegin {
+    /// Specifies the horizontal draw start position in units of dots.
+    #[serde(rename = "@x")]
+    pub x: u16,
+    #[serde(rename = "@style", skip_serializing_if = "Option::is_none")]
+    pub style: Option<Style>
+}
+
+impl NormalItem for VlineBegin {}
+
+/// Stop drawing a vertical line started by a `VlineBegin`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename ="vline-end")]
+pub struct VlineEnd {
+    /// Specifies the horizontal draw end position in units of dots. Must be the same value as the matching `VlineBegin`.
+    #[serde(rename = "@x")]
+    pub x: u16,
+    #[serde(rename = "@style", skip_serializing_if = "Option::is_none")]
+    pub style: Option<Style>
+}
+
+impl NormalItem for VlineEnd {}
+
+/// Send a pulse to the drawer kick-out connector, usually to open a cash drawer.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename ="pulse")]
+pub struct Pulse {
+    /// Specifies the connector pin. Defaults to `Drawer::Drawer1` (pin 2).
+    #[serde(rename = "@drawer", skip_serializing_if = "Option::is_none")]
+    pub drawer: Option<Drawer>,
+    /// Specifies the pulse on/off time. Defaults to `PulseTime::Pulse100`.
+    #[serde(rename = "@time", skip_serializing_if = "Option::is_none")]
+    pub time: Option<PulseTime>
+}
+
+impl NormalItem for Pulse {}
+
+/// Recover the printer from a recoverable error, such as an autocutter error.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename ="recovery")]
+pub struct Recovery {}
+
+impl NormalItem for Recovery {}
+
+/// Reset the printer.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename ="reset")]
+pub struct Reset {}
+
+impl NormalItem for Reset {}
+
 
 #[cfg(test)]
 mod tests{
-    use crate::formatters::Style;
+    use crate::formatters::{Drawer, PulseTime, Style};
 
-    use super::{Cut, Hline};
+    use super::{Cut, Hline, Pulse, Recovery, Reset, VlineBegin, VlineEnd};
  
     #[test]
     fn test_hline() {
@@ -45,6 +158,32 @@ mod tests{
         assert_eq!(out, String::from(r#"<hline x1="1" x2="2" style="medium"/>"#));
     }
 
+    #[test]
+    fn test_vline() {
+        let begin = VlineBegin{x: 100, style: Some(Style::Thin)};
+        let out = quick_xml::se::to_string(&begin).unwrap();
+        assert_eq!(out, String::from(r#"<vline-begin x="100" style="thin"/>"#));
+
+        let end = VlineEnd{x: 100, style: None};
+        let out = quick_xml::se::to_string(&end).unwrap();
+        assert_eq!(out, String::from(r#"<vline-end x="100"/>"#));
+    }
+
+    #[test]
+    fn test_pulse() {
+        let test = Pulse{drawer: Some(Drawer::Drawer2), time: Some(PulseTime::Pulse300)};
+        let out = quick_xml::se::to_string(&test).unwrap();
+        assert_eq!(out, String::from(r#"<pulse drawer="drawer_2" time="pulse_300"/>"#));
+    }
+
+    #[test]
+    fn test_recovery_reset() {
+        let out = quick_xml::se::to_string(&Recovery{}).unwrap();
+        assert_eq!(out, String::from(r#"<recovery/>"#));
+        let out = quick_xml::se::to_string(&Reset{}).unwrap();
+        assert_eq!(out, String::from(r#"<reset/>"#));
+    }
+
     #[test]
     fn test_cut() {
         let est = Cut{cut_type: crate::formatters::CutType::Feed};
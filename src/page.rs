@@ -1,11 +1,60 @@
 //! Types that are exclusive to page mode.
+use quick_xml::DeError;
 use serde::{Deserialize, Serialize};
 
-use crate::formatters::Style;
+use crate::{formatters::Style, universal::{Barcode, Command, Feed, Image, Raw, Sound, Symbol, Text}};
 
-pub trait PageItem: Serialize {}
+pub trait PageItem: Serialize + Into<PageElement> {}
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+/// A single command in a page mode document.
+#[derive(Debug, Clone)]
+pub enum PageElement {
+    Area(Area),
+    Rectangle(Rectangle),
+    Text(Text),
+    Feed(Feed),
+    Barcode(Barcode),
+    Symbol(Symbol),
+    Image(Image),
+    Sound(Sound),
+    Command(Command),
+    /// An element passed through unchanged
+    Raw(Raw)
+}
+
+impl PageElement {
+    /// Serialize the element to its ePOS XML representation.
+    pub fn to_xml(&self) -> Result<String, DeError> {
+        match self {
+            PageElement::Area(item) => quick_xml::se::to_string(item),
+            PageElement::Rectangle(item) => quick_xml::se::to_string(item),
+            PageElement::Text(item) => quick_xml::se::to_string(item),
+            PageElement::Feed(item) => quick_xml::se::to_string(item),
+            PageElement::Barcode(item) => quick_xml::se::to_string(item),
+            PageElement::Symbol(item) => quick_xml::se::to_string(item),
+            PageElement::Image(item) => quick_xml::se::to_string(item),
+            PageElement::Sound(item) => quick_xml::se::to_string(item),
+            PageElement::Command(item) => quick_xml::se::to_string(item),
+            PageElement::Raw(item) => Ok(item.xml().to_string()),
+        }
+    }
+}
+
+macro_rules! page_element_from {
+    ($($item:ident),*) => {
+        $(
+            impl From<$item> for PageElement {
+                fn from(item: $item) -> Self {
+                    PageElement::$item(item)
+                }
+            }
+        )*
+    };
+}
+
+page_element_from!(Area, Rectangle, Text, Feed, Barcode, Symbol, Image, Sound, Command, Raw);
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename ="area")]
 pub struct Area {
         /// Start point for the print area
@@ -26,7 +75,7 @@ impl PageItem for Area {}
 
 
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename ="rectangle")]
 pub struct Rectangle {
     /// Specifies the horizontal draw start position in units of dots.
@@ -0,0 +1,276 @@
+//! Read ePOS-Print XML back into typed items.
+//!
+//! Either a complete SOAP envelope or a bare `epos-print` element can be parsed.
+//! Use `Builder::load_normal` or `Builder::load_page` to turn a stored document into a builder that can be edited and printed again.
+//!
+//! ```rust
+//! use epos_rs::{normal::NormalElement, parse::{parse, Document, UnknownElements}};
+//!
+//! let xml = r#"<epos-print xmlns="http://www.epson-pos.com/schemas/2011/03/epos-print">
+//!     <text dw="true">Fish &amp; Chips&#10;</text><logo key1="32" key2="32"/><cut type="feed"/>
+//! </epos-print>"#;
+//!
+//! assert!(parse(xml, UnknownElements::Reject).is_err());
+//! let Document::Normal { items, .. } = parse(xml, UnknownElements::Keep).unwrap() else { panic!() };
+//! assert!(matches!(&items[0], NormalElement::Text(text) if text.text == "Fish & Chips\n"));
+//! assert!(matches!(&items[1], NormalElement::Raw(raw) if raw.name() == "logo"));
+//! ```
+
+use quick_xml::{events::Event, Reader};
+use serde::de::DeserializeOwned;
+
+use crate::{error::EPOSError, normal::{Cut, Hline, NormalElement, Pulse, Recovery, Reset, VlineBegin, VlineEnd},
+    page::{Area, PageElement, Rectangle}, universal::{Barcode, Command, Feed, Image, Layout, Raw, Sound, Symbol, Text}};
+
+/// How to handle elements that this crate has no type for.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UnknownElements {
+    /// Return an error naming the element and its position.
+    #[default]
+    Reject,
+    /// Keep the element as a `Raw` item, which is sent to the printer unchanged.
+    Keep
+}
+
+/// The contents of an `epos-print` element.
+#[derive(Debug, Clone)]
+pub enum Document {
+    /// A normal mode document
+    Normal {
+        layout: Option<Layout>,
+        items: Vec<NormalElement>
+    },
+    /// A page mode document, with the items of its `page` element
+    Page {
+        layout: Option<Layout>,
+        items: Vec<PageElement>
+    }
+}
+
+/// An element read from the document.
+struct Node<'a> {
+    /// Element name without a namespace prefix
+    name: String,
+    /// Byte offset of the element in the document
+    position: usize,
+    /// The start tag, including its attributes
+    start_tag: &'a str,
+    /// The complete element
+    outer: &'a str,
+    /// The contents of the element, or `None` for an empty element
+    inner: Option<(&'a str, usize)>
+}
+
+impl Node<'_> {
+    fn error(&self, reason: impl std::fmt::Display) -> EPOSError {
+        EPOSError::DocumentError { position: self.position, reason: format!("<{}>: {}", self.name, reason) }
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self) -> Result<T, EPOSError> {
+        quick_xml::de::from_str(self.outer).map_err(|err| self.error(err))
+    }
+
+    /// Deserialize an element with text content, keeping the text exactly as written.
+    /// The deserializer trims whitespace, which is significant in receipts.
+    fn deserialize_text<T: DeserializeOwned>(&self) -> Result<(T, String), EPOSError> {
+        let tag = self.start_tag.trim_end_matches('>').trim_end_matches('/');
+        let qualified = tag.trim_start_matches('<').split(|c: char| c.is_whitespace()).next().unwrap_or_default();
+        let item = quick_xml::de::from_str(&format!("{}>_</{}>", tag, qualified)).map_err(|err| self.error(err))?;
+        let raw = self.inner.map(|(inner, _)| inner).unwrap_or_default();
+        let text = quick_xml::escape::unescape(raw).map_err(|err| self.error(err))?;
+        Ok((item, text.into_owned()))
+    }
+}
+
+/// Parse a SOAP envelope or a bare `epos-print` element.
+/// The document is in page mode if its only item is a `page` element.
+pub fn parse(xml: &str, unknown: UnknownElements) -> Result<Document, EPOSError> {
+    let (body, offset) = find_epos_print(xml)?;
+    let nodes = read_children(body, offset)?;
+
+    let mut layout = None;
+    let mut items = Vec::new();
+    for node in nodes {
+        if node.name == "layout" {
+            layout = Some(node.deserialize()?);
+        } else {
+            items.push(node);
+        }
+    }
+
+    if let [page] = items.as_slice() {
+        if page.name == "page" {
+            let (inner, offset) = page.inner.unwrap_or(("", page.position));
+            let items = read_children(inner, offset)?.iter()
+                .map(|node| page_element(node, unknown))
+                .collect::<Result<_, _>>()?;
+            return Ok(Document::Page { layout, items });
+        }
+    }
+
+    let items = items.iter().map(|node| match node.name.as_str() {
+        "page" if unknown == UnknownElements::Reject => Err(node.error("a page can only be read if it is the only item in the document")),
+        _ => normal_element(node, unknown)
+    }).collect::<Result<_, _>>()?;
+    Ok(Document::Normal { layout, items })
+}
+
+/// Return the contents of the first `epos-print` element, and its byte offset.
+fn find_epos_print(xml: &str) -> Result<(&str, usize), EPOSError> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        let position = reader.buffer_position();
+        let err = |err: quick_xml::Error| EPOSError::DocumentError { position, reason: err.to_string() };
+        match reader.read_event().map_err(err)? {
+            Event::Start(start) if start.local_name().as_ref() == b"epos-print" => {
+                let span = reader.read_to_end(start.name()).map_err(err)?;
+                return Ok((&xml[span.clone()], span.start));
+            },
+            Event::Empty(start) if start.local_name().as_ref() == b"epos-print" => return Ok(("", position)),
+            Event::Eof => return Err(EPOSError::DocumentError { position, reason: "no epos-print element".to_string() }),
+            _ => {}
+        }
+    }
+}
+
+/// Split a fragment into its top level elements. `offset` is the position of the fragment in the document.
+fn read_children(fragment: &str, offset: usize) -> Result<Vec<Node<'_>>, EPOSError> {
+    let mut reader = Reader::from_str(fragment);
+    let mut nodes = Vec::new();
+    loop {
+        let start = reader.buffer_position();
+        let position = offset + start;
+        let err = |err: quick_xml::Error| EPOSError::DocumentError { position, reason: err.to_string() };
+        match reader.read_event().map_err(err)? {
+            Event::Start(tag) => {
+                let name = String::from_utf8_lossy(tag.local_name().as_ref()).to_string();
+                let tag_end = reader.buffer_position();
+                let span = reader.read_to_end(tag.name()).map_err(err)?;
+                nodes.push(Node { name, position, start_tag: &fragment[start..tag_end], outer: &fragment[start..reader.buffer_position()],
+                    inner: Some((&fragment[span.clone()], offset + span.start)) });
+            },
+            Event::Empty(tag) => {
+                let name = String::from_utf8_lossy(tag.local_name().as_ref()).to_string();
+                let outer = &fragment[start..reader.buffer_position()];
+                nodes.push(Node { name, position, start_tag: outer, outer, inner: None });
+            },
+            Event::Text(text) if !text.iter().all(u8::is_ascii_whitespace) =>
+                return Err(EPOSError::DocumentError { position, reason: "unexpected text outside of an element".to_string() }),
+            Event::Eof => return Ok(nodes),
+            _ => {}
+        }
+    }
+}
+
+fn unknown_element(node: &Node, unknown: UnknownElements) -> Result<Raw, EPOSError> {
+    match unknown {
+        UnknownElements::Reject => Err(node.error("unsupported element")),
+        UnknownElements::Keep => Ok(Raw::new(&node.name, node.outer))
+    }
+}
+
+fn text(node: &Node) -> Result<Text, EPOSError> {
+    let (mut item, text): (Text, _) = node.deserialize_text()?;
+    item.text = text;
+    Ok(item)
+}
+
+fn barcode(node: &Node) -> Result<Barcode, EPOSError> {
+    let (mut item, text): (Barcode, _) = node.deserialize_text()?;
+    item.set_text(text);
+    Ok(item)
+}
+
+fn symbol(node: &Node) -> Result<Symbol, EPOSError> {
+    let (mut item, text): (Symbol, _) = node.deserialize_text()?;
+    item.text = text;
+    Ok(item)
+}
+
+fn normal_element(node: &Node, unknown: UnknownElements) -> Result<NormalElement, EPOSError> {
+    Ok(match node.name.as_str() {
+        "text" => text(node)?.into(),
+        "feed" => node.deserialize::<Feed>()?.into(),
+        "barcode" => barcode(node)?.into(),
+        "symbol" => symbol(node)?.into(),
+        "image" => node.deserialize::<Image>()?.into(),
+        "sound" => node.deserialize::<Sound>()?.into(),
+        "command" => node.deserialize::<Command>()?.into(),
+        "cut" => node.deserialize::<Cut>()?.into(),
+        "hline" => node.deserialize::<Hline>()?.into(),
+        "vline-begin" => node.deserialize::<VlineBegin>()?.into(),
+        "vline-end" => node.deserialize::<VlineEnd>()?.into(),
+        "pulse" => node.deserialize::<Pulse>()?.into(),
+        "recovery" => node.deserialize::<Recovery>()?.into(),
+        "reset" => node.deserialize::<Reset>()?.into(),
+        _ => unknown_element(node, unknown)?.into()
+    })
+}
+
+fn page_element(node: &Node, unknown: UnknownElements) -> Result<PageElement, EPOSError> {
+    Ok(match node.name.as_str() {
+        "area" => node.deserialize::<Area>()?.into(),
+        "rectangle" => node.deserialize::<Rectangle>()?.into(),
+        "text" => text(node)?.into(),
+        "feed" => node.deserialize::<Feed>()?.into(),
+        "barcode" => barcode(node)?.into(),
+        "symbol" => symbol(node)?.into(),
+        "image" => node.deserialize::<Image>()?.into(),
+        "sound" => node.deserialize::<Sound>()?.into(),
+        "command" => node.deserialize::<Command>()?.into(),
+        _ => unknown_element(node, unknown)?.into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::EPOSError, normal::NormalElement, page::PageElement, soap::{envelope, EnumBody}, universal::{Barcode, Text}, barcodes::BarcodeType};
+
+    use super::{parse, Document, UnknownElements};
+
+    #[test]
+    fn test_round_trip() {
+        let items: Vec<NormalElement> = vec![
+            Text { text: "  Fish & Chips <large>\n\n".to_string(), double_width: Some(true), ..Default::default() }.into(),
+            Barcode::builder(BarcodeType::Code39, "AB 12 ").build().unwrap().into(),
+            crate::normal::Cut { cut_type: crate::formatters::CutType::Feed }.into(),
+        ];
+        let xml = envelope(&EnumBody::NoPage { layout: None, body: &items }, None).unwrap();
+        let Document::Normal { layout, items: parsed } = parse(&xml, UnknownElements::Reject).unwrap() else { panic!("expected normal mode") };
+        assert!(layout.is_none());
+        assert_eq!(envelope(&EnumBody::NoPage { layout: None, body: &parsed }, None).unwrap(), xml);
+        assert!(matches!(&parsed[0], NormalElement::Text(text) if text.text == "  Fish & Chips <large>\n\n"));
+    }
+
+    #[test]
+    fn test_page() {
+        let xml = concat!(r#"<epos-print xmlns="http://www.epson-pos.com/schemas/2011/03/epos-print">"#,
+            r#"<layout type="label" width="580"/>"#,
+            "<page>\n",
+            r#"  <area x="0" y="0" width="500" height="500"/><position x="10" y="40"/>"#, "\n",
+            r#"  <text>hi</text>"#,
+            "</page></epos-print>");
+        let err = parse(xml, UnknownElements::Reject).unwrap_err();
+        assert!(matches!(err, EPOSError::DocumentError { position, reason } if position == xml.find("<position").unwrap() && reason == "<position>: unsupported element"));
+
+        let Document::Page { layout, items } = parse(xml, UnknownElements::Keep).unwrap() else { panic!("expected page mode") };
+        assert_eq!(layout.unwrap().width, Some(580));
+        assert_eq!(items.len(), 3);
+        assert!(matches!(&items[1], PageElement::Raw(raw) if raw.xml() == r#"<position x="10" y="40"/>"#));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse("<s:Envelope/>", UnknownElements::Keep).unwrap_err();
+        assert!(matches!(err, EPOSError::DocumentError { reason, .. } if reason == "no epos-print element"));
+
+        let err = parse(r#"<epos-print><feed line="x"/></epos-print>"#, UnknownElements::Keep).unwrap_err();
+        assert!(matches!(err, EPOSError::DocumentError { position: 12, reason } if reason.starts_with("<feed>")));
+
+        let err = parse("<epos-print>hello</epos-print>", UnknownElements::Keep).unwrap_err();
+        assert!(matches!(err, EPOSError::DocumentError { position: 12, .. }));
+
+        let err = parse("<epos-print><text>a</text><page><text>b</text></page></epos-print>", UnknownElements::Reject).unwrap_err();
+        assert!(matches!(err, EPOSError::DocumentError { position: 26, .. }));
+    }
+}
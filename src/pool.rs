@@ -0,0 +1,430 @@
+//! Routing documents across several printers, such as the front counter, kitchen and bar printers of one location.
+//!
+//! Printers are registered under a name with a set of tags, and documents are printed to a tag rather than to a printer.
+//! The printers with a tag are tried in the order they were added, so the first is the primary and the rest are its backups.
+//! When a printer is offline, has its cover open, or times out, the document is sent to the next printer with the tag.
+//! With round robin, documents are spread across all available printers with the tag instead.
+//!
+//! The pool keeps the health of each printer from the responses it gets, and `check_health` refreshes it with a status query.
+//! Printers that need attention are tried last.
+//!
+//! A print that times out, or whose connection drops, may still be printed by the printer. Give documents a print job ID,
+//! so the printer is asked about the job before the document is sent to a backup.
+//!
+//! ```rust,no_run
+//! # tokio_test::block_on(async {
+//! use epos_rs::{pool::PrinterPool, universal::Text, Builder};
+//!
+//! let mut pool = PrinterPool::new();
+//! pool.add("kitchen-1", &["kitchen"], Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap());
+//! pool.add("kitchen-2", &["kitchen", "bar"], Builder::new(10000, "local_printer", "http://192.168.1.195").unwrap());
+//!
+//! let mut ticket = pool.builder("kitchen-1").unwrap().normal();
+//! ticket.add(Text{text: String::from("2x Pad Thai\n"), ..Default::default()}).unwrap();
+//! ticket.set_job_id("order-1042").unwrap();
+//! let receipt = pool.print_normal("kitchen", &ticket).await.unwrap();
+//! println!("printed on {}", receipt.printer);
+//! # })
+//! ```
+
+use std::{collections::HashMap, sync::Mutex};
+
+use futures_util::future::join_all;
+use log::{debug, warn};
+
+use crate::{error::EPOSError, retry::is_connect_error, send, status::{EposCode, JobStatus, PrintReceipt, PrinterStatus, Response, Severity},
+    transport::{EnumBody, Request}, Builder, NormalBuilder, PageBuilder};
+
+/// The health of a printer in the pool, as last reported by the printer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// The printer hasn't been used or checked yet
+    Unknown,
+    /// The printer can print. The status may still carry warnings, such as paper near end
+    Ready(PrinterStatus),
+    /// The printer is reachable, but can't print until someone attends to it, such as closing the cover
+    NeedsAttention(PrinterStatus),
+    /// The printer could not be reached
+    Unreachable
+}
+
+impl Health {
+    /// Bits that mean the printer can't print, even though the severity may not say so.
+    const UNAVAILABLE: PrinterStatus = PrinterStatus::OFFLINE.union(PrinterStatus::NO_RESPONSE).union(PrinterStatus::COVER_OPEN);
+
+    /// The health of a printer reporting this status.
+    pub fn from_status(status: PrinterStatus) -> Health {
+        if status.intersects(Health::UNAVAILABLE) || status.severity() >= Severity::Error {
+            Health::NeedsAttention(status)
+        } else {
+            Health::Ready(status)
+        }
+    }
+
+    /// Returns true if documents should be sent to the printer. Printers that haven't been checked yet are assumed to be available.
+    pub fn is_available(&self) -> bool {
+        matches!(self, Health::Unknown | Health::Ready(_))
+    }
+}
+
+/// The result of a print through the pool.
+#[derive(Debug)]
+pub struct PoolReceipt {
+    /// Name of the printer that printed the document
+    pub printer: String,
+    /// The printer's receipt
+    pub receipt: PrintReceipt
+}
+
+#[derive(Debug)]
+struct Member {
+    name: String,
+    tags: Vec<String>,
+    builder: Builder,
+    health: Mutex<Health>
+}
+
+impl Member {
+    fn set_health(&self, health: Health) {
+        let mut current = self.health.lock().unwrap();
+        if *current != health {
+            debug!("printer {} is now {:?}", self.name, health);
+        }
+        *current = health;
+    }
+
+    /// Update the health from the outcome of a request.
+    fn record(&self, result: &Result<Response, EPOSError>) {
+        match result {
+            Ok(resp) | Err(EPOSError::ResponseError { status: resp }) => self.set_health(Health::from_status(resp.printer_status())),
+            Err(EPOSError::NetworkError(_) | EPOSError::IoError(_)) => self.set_health(Health::Unreachable),
+            Err(_) => {}
+        }
+    }
+}
+
+/// A set of named, tagged printers. Documents are routed by tag, with failover and optional round robin.
+#[derive(Debug, Default)]
+pub struct PrinterPool {
+    printers: Vec<Member>,
+    round_robin: bool,
+    /// Number of prints routed per tag, to pick the next printer in round robin
+    turns: Mutex<HashMap<String, usize>>
+}
+
+impl PrinterPool {
+    /// Create an empty pool.
+    pub fn new() -> PrinterPool {
+        PrinterPool::default()
+    }
+
+    /// Add a printer with the given tags. Printers are tried in the order they are added, so add the primary printer for a tag first.
+    /// A printer already registered under `name` is replaced.
+    pub fn add<S: Into<String>>(&mut self, name: S, tags: &[&str], builder: Builder) {
+        let member = Member {
+            name: name.into(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            builder,
+            health: Mutex::new(Health::Unknown)
+        };
+        match self.printers.iter_mut().find(|printer| printer.name == member.name) {
+            Some(printer) => *printer = member,
+            None => self.printers.push(member)
+        }
+    }
+
+    /// Spread documents across all available printers with a tag, instead of always preferring the first.
+    pub fn set_round_robin(&mut self, round_robin: bool) {
+        self.round_robin = round_robin;
+    }
+
+    /// The connection to the named printer, such as to build a document, or to send a request to that printer only.
+    pub fn builder(&self, name: &str) -> Option<&Builder> {
+        self.printers.iter().find(|printer| printer.name == name).map(|printer| &printer.builder)
+    }
+
+    /// The last known health of the named printer.
+    pub fn health(&self, name: &str) -> Option<Health> {
+        self.printers.iter().find(|printer| printer.name == name).map(|printer| *printer.health.lock().unwrap())
+    }
+
+    /// Query the status of every printer at once, and return their health by name, in the order they were added.
+    pub async fn check_health(&self) -> Vec<(String, Health)> {
+        join_all(self.printers.iter().map(|printer| async move {
+            let result = printer.builder.status().await;
+            if let Err(err) = &result {
+                warn!("printer {} status check failed: {}", printer.name, err);
+            }
+            printer.record(&result);
+            (printer.name.clone(), *printer.health.lock().unwrap())
+        })).await
+    }
+
+    /// Check a normal mode document and print it on a printer with the tag.
+    pub async fn print_normal(&self, tag: &str, handler: &NormalBuilder) -> Result<PoolReceipt, EPOSError> {
+        handler.check()?;
+        self.print(tag, EnumBody::NoPage { layout: handler.layout.as_ref(), body: &handler.build }, handler.job_id.as_deref()).await
+    }
+
+    /// Check a page mode document and print it on a printer with the tag.
+    pub async fn print_page(&self, tag: &str, handler: &PageBuilder) -> Result<PoolReceipt, EPOSError> {
+        handler.check()?;
+        self.print(tag, EnumBody::Page { layout: handler.layout.as_ref(), body: &handler.build }, handler.job_id.as_deref()).await
+    }
+
+    /// The printers with a tag, in the order to try them: available printers first, then those that need attention.
+    fn candidates(&self, tag: &str) -> Result<Vec<&Member>, EPOSError> {
+        let tagged = self.printers.iter().filter(|printer| printer.tags.iter().any(|t| t == tag));
+        let (mut available, unavailable): (Vec<&Member>, Vec<&Member>) = tagged.partition(|printer| printer.health.lock().unwrap().is_available());
+        if available.is_empty() && unavailable.is_empty() {
+            return Err(EPOSError::NoPrinterError(tag.to_string()));
+        }
+        if self.round_robin && !available.is_empty() {
+            let mut turns = self.turns.lock().unwrap();
+            let turn = turns.entry(tag.to_string()).or_default();
+            let len = available.len();
+            available.rotate_left(*turn % len);
+            *turn = turn.wrapping_add(1);
+        }
+        available.extend(unavailable);
+        Ok(available)
+    }
+
+    async fn print(&self, tag: &str, body: EnumBody<'_>, job_id: Option<&str>) -> Result<PoolReceipt, EPOSError> {
+        let mut last_err = None;
+        for printer in self.candidates(tag)? {
+            let builder = &printer.builder;
+            let request = Request { body, dev_id: &builder.dev_id, timeout: builder.timeout, job_id };
+            let result = send(builder.transport.as_ref(), &builder.retry, request).await;
+            printer.record(&result);
+            let err = match result {
+                Ok(response) => return Ok(PoolReceipt { printer: printer.name.clone(), receipt: PrintReceipt { job_id: job_id.map(String::from), response } }),
+                Err(err) => err
+            };
+            if !fails_over(&err) {
+                return Err(err);
+            }
+            // the printer may still print the document, so only fail over once it's known not to have the job
+            if let Some(job_id) = job_id.filter(|_| may_have_printed(&err)) {
+                match builder.job_status(job_id).await {
+                    Ok(JobStatus::Printed(response)) => {
+                        printer.set_health(Health::from_status(response.printer_status()));
+                        return Ok(PoolReceipt { printer: printer.name.clone(), receipt: PrintReceipt { job_id: Some(job_id.to_string()), response } });
+                    },
+                    Ok(JobStatus::NotFound(_)) => {},
+                    _ => return Err(err)
+                }
+            }
+            warn!("printer {} failed, trying the next printer tagged {:?}: {}", printer.name, tag, err);
+            last_err = Some(err);
+        }
+        Err(last_err.unwrap_or_else(|| EPOSError::NoPrinterError(tag.to_string())))
+    }
+}
+
+fn is_network_error(err: &EPOSError) -> bool {
+    matches!(err, EPOSError::NetworkError(_) | EPOSError::IoError(_))
+}
+
+/// Returns true if the connection to the printer could not be made, so it can't have received the document.
+fn is_connect_failure(err: &EPOSError) -> bool {
+    match err {
+        EPOSError::NetworkError(err) => err.is_connect(),
+        EPOSError::IoError(err) => is_connect_error(err),
+        _ => false
+    }
+}
+
+/// Returns true if the printer may print the document despite `err`: it may have received the document before the connection failed,
+/// or it may still finish a print that timed out.
+fn may_have_printed(err: &EPOSError) -> bool {
+    match err {
+        EPOSError::ResponseError { status } => status.code == EposCode::Timeout,
+        err => is_network_error(err) && !is_connect_failure(err)
+    }
+}
+
+/// Returns true if another printer should be tried after a print failed with `err`.
+fn fails_over(err: &EPOSError) -> bool {
+    match err {
+        EPOSError::ResponseError { status } => {
+            matches!(status.code, EposCode::Timeout | EposCode::CoverOpen)
+                || !Health::from_status(status.printer_status()).is_available()
+        },
+        err => is_network_error(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::{error::EPOSError, status::{EposCode, PrinterStatus, Response}, transport::RecordingTransport, universal::Text, Builder};
+
+    use super::{Health, PrinterPool};
+
+    fn ready() -> PrinterStatus {
+        PrinterStatus::RESERVED_FIXED | PrinterStatus::PRINT_SUCCESS
+    }
+
+    fn pool(printers: &[(&str, &[&str])]) -> (PrinterPool, Vec<RecordingTransport>) {
+        let mut pool = PrinterPool::new();
+        let recorders: Vec<RecordingTransport> = printers.iter().map(|_| RecordingTransport::new()).collect();
+        for ((name, tags), recorder) in printers.iter().zip(&recorders) {
+            pool.add(*name, tags, Builder::with_transport(10000, "local_printer", recorder.clone()));
+        }
+        (pool, recorders)
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let (pool, recorders) = pool(&[("kitchen-1", &["kitchen"]), ("kitchen-2", &["kitchen", "bar"]), ("counter", &["front"])]);
+        let mut ticket = pool.builder("kitchen-1").unwrap().normal();
+        ticket.add(Text { text: "2x Pad Thai\n".to_string(), ..Default::default() }).unwrap();
+
+        let cover_open = ready() | PrinterStatus::COVER_OPEN;
+        recorders[0].push_response(Response { status: cover_open.bits(), ..Response::failure(EposCode::CoverOpen) });
+        assert_eq!(pool.print_normal("kitchen", &ticket).await.unwrap().printer, "kitchen-2");
+        assert_eq!(pool.health("kitchen-1"), Some(Health::NeedsAttention(cover_open)));
+        assert_eq!(pool.health("kitchen-2"), Some(Health::Ready(PrinterStatus::from(0x12))));
+
+        // the primary needs attention, so the backup is tried first
+        assert_eq!(pool.print_normal("kitchen", &ticket).await.unwrap().printer, "kitchen-2");
+        assert_eq!(recorders[0].requests().len(), 1);
+
+        // the backup can't be reached, so the primary is tried even though it may still need attention
+        recorders[1].push_error(io::Error::from(io::ErrorKind::ConnectionRefused).into());
+        assert_eq!(pool.print_normal("kitchen", &ticket).await.unwrap().printer, "kitchen-1");
+        assert_eq!(pool.health("kitchen-2"), Some(Health::Unreachable));
+
+        // the document itself is wrong, so another printer won't print it either
+        recorders[0].push_response(Response::failure(EposCode::SchemaError));
+        assert!(matches!(pool.print_normal("kitchen", &ticket).await, Err(EPOSError::ResponseError { status }) if status.code == EposCode::SchemaError));
+        assert_eq!(recorders[1].requests().len(), 3);
+
+        recorders[0].push_response(Response::failure(EposCode::Timeout));
+        recorders[1].push_response(Response::failure(EposCode::Timeout));
+        assert!(matches!(pool.print_normal("kitchen", &ticket).await, Err(EPOSError::ResponseError { status }) if status.code == EposCode::Timeout));
+        assert!(matches!(pool.print_normal("patio", &ticket).await, Err(EPOSError::NoPrinterError(tag)) if tag == "patio"));
+        assert!(recorders[2].requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failover_after_timeout() {
+        let (pool, recorders) = pool(&[("bar-1", &["bar"]), ("bar-2", &["bar"])]);
+        let mut ticket = pool.builder("bar-1").unwrap().normal();
+        ticket.add(Text { text: "1x Negroni\n".to_string(), ..Default::default() }).unwrap();
+
+        // without a job ID, it's unknown whether the primary printed
+        recorders[0].push_error(io::Error::from(io::ErrorKind::TimedOut).into());
+        assert_eq!(pool.print_normal("bar", &ticket).await.unwrap().printer, "bar-2");
+
+        assert_eq!(pool.health("bar-1"), Some(Health::Unreachable));
+        pool.check_health().await;
+
+        // the primary printed before the connection dropped, so the backup isn't used
+        ticket.set_job_id("order-7").unwrap();
+        recorders[0].push_error(io::Error::from(io::ErrorKind::TimedOut).into());
+        let receipt = pool.print_normal("bar", &ticket).await.unwrap();
+        assert_eq!(receipt.printer, "bar-1");
+        assert_eq!(receipt.receipt.job_id.as_deref(), Some("order-7"));
+        assert_eq!(recorders[0].requests().len(), 4);
+        assert_eq!(recorders[1].requests().len(), 2);
+
+        // the primary never received it, so the backup prints it
+        recorders[0].push_error(io::Error::from(io::ErrorKind::TimedOut).into());
+        recorders[0].push_response(Response::failure(EposCode::JobNotFound));
+        assert_eq!(pool.print_normal("bar", &ticket).await.unwrap().printer, "bar-2");
+    }
+
+    #[tokio::test]
+    async fn test_failover_after_epos_timeout() {
+        let (pool, recorders) = pool(&[("kitchen-1", &["kitchen"]), ("kitchen-2", &["kitchen"])]);
+        let mut ticket = pool.builder("kitchen-1").unwrap().normal();
+        ticket.add(Text { text: "2x Pad Thai\n".to_string(), ..Default::default() }).unwrap();
+        ticket.set_job_id("order-9").unwrap();
+
+        // the print didn't finish within the timeout, but the primary completed it afterwards
+        recorders[0].push_response(Response::failure(EposCode::Timeout));
+        let receipt = pool.print_normal("kitchen", &ticket).await.unwrap();
+        assert_eq!(receipt.printer, "kitchen-1");
+        assert_eq!(receipt.receipt.job_id.as_deref(), Some("order-9"));
+        let requests = recorders[0].requests();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[1].envelope.contains("<text>"));
+        assert!(recorders[1].requests().is_empty());
+
+        // the primary has no record of it, so the backup prints it
+        recorders[0].push_response(Response::failure(EposCode::Timeout));
+        recorders[0].push_response(Response::failure(EposCode::JobNotFound));
+        assert_eq!(pool.print_normal("kitchen", &ticket).await.unwrap().printer, "kitchen-2");
+    }
+
+    #[tokio::test]
+    async fn test_no_failover_while_unknown() {
+        let status_replies: [Result<Response, EPOSError>; 3] = [
+            // the primary is still printing it
+            Ok(Response::failure(EposCode::Printing)),
+            // it failed on the primary after it was received
+            Ok(Response::failure(EposCode::CoverOpen)),
+            // the primary can't say what became of it
+            Err(io::Error::from(io::ErrorKind::ConnectionReset).into())
+        ];
+        for reply in status_replies {
+            let (pool, recorders) = pool(&[("bar-1", &["bar"]), ("bar-2", &["bar"])]);
+            let mut ticket = pool.builder("bar-1").unwrap().normal();
+            ticket.add(Text { text: "1x Negroni\n".to_string(), ..Default::default() }).unwrap();
+            ticket.set_job_id("order-8").unwrap();
+
+            recorders[0].push_error(io::Error::from(io::ErrorKind::TimedOut).into());
+            match reply {
+                Ok(resp) => recorders[0].push_response(resp),
+                Err(err) => recorders[0].push_error(err)
+            }
+            assert!(matches!(pool.print_normal("bar", &ticket).await, Err(EPOSError::IoError(err)) if err.kind() == io::ErrorKind::TimedOut));
+            assert_eq!(recorders[0].requests().len(), 2);
+            assert!(recorders[1].requests().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_robin() {
+        let (mut pool, recorders) = pool(&[("bar-1", &["bar"]), ("bar-2", &["bar"]), ("bar-3", &["bar"])]);
+        pool.set_round_robin(true);
+        let mut ticket = pool.builder("bar-1").unwrap().normal();
+        ticket.add(Text { text: "1x Negroni\n".to_string(), ..Default::default() }).unwrap();
+
+        let mut printed = Vec::new();
+        for _ in 0..4 {
+            printed.push(pool.print_normal("bar", &ticket).await.unwrap().printer);
+        }
+        assert_eq!(printed, ["bar-1", "bar-2", "bar-3", "bar-1"]);
+
+        // printers that need attention are skipped
+        recorders[1].push_response(Response { status: (ready() | PrinterStatus::OFFLINE).bits(), ..Response::failure(EposCode::Empty) });
+        pool.check_health().await;
+        printed.clear();
+        for _ in 0..4 {
+            printed.push(pool.print_normal("bar", &ticket).await.unwrap().printer);
+        }
+        assert_eq!(printed, ["bar-1", "bar-3", "bar-1", "bar-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_check_health() {
+        let (pool, recorders) = pool(&[("counter", &["front"]), ("kitchen", &["kitchen"]), ("bar", &["bar"])]);
+        assert_eq!(pool.health("counter"), Some(Health::Unknown));
+        assert_eq!(pool.health("patio"), None);
+
+        recorders[0].push_response(Response { success: true, status: (ready() | PrinterStatus::RECEIPT_NEAR_END).bits(), ..Default::default() });
+        recorders[1].push_response(Response { success: true, status: (ready() | PrinterStatus::RECEIPT_END).bits(), ..Default::default() });
+        recorders[2].push_error(io::Error::from(io::ErrorKind::ConnectionRefused).into());
+        let health = pool.check_health().await;
+        assert_eq!(health, [
+            ("counter".to_string(), Health::Ready(ready() | PrinterStatus::RECEIPT_NEAR_END)),
+            ("kitchen".to_string(), Health::NeedsAttention(ready() | PrinterStatus::RECEIPT_END)),
+            ("bar".to_string(), Health::Unreachable)
+        ]);
+        assert!(health[0].1.is_available() && !health[1].1.is_available() && !health[2].1.is_available());
+    }
+}
@@ -0,0 +1,604 @@
+//! Render an approximate preview of a document without a printer.
+//!
+//! Items are laid out in printer dots using the metrics of a typical 180 dpi thermal printer,
+//! then written out as SVG, or as PNG with the `image` feature. The preview is meant for checking layouts:
+//! text uses a generic monospace font, and barcodes and symbols are drawn as labelled placeholder boxes.
+//!
+//! ```rust
+//! use epos_rs::{Builder, normal::Cut, formatters::{Align, CutType}, preview::{Preview, PreviewOptions}, raster::PAPER_WIDTH_58MM, universal::Text};
+//!
+//! let mut handler = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap().normal();
+//! handler.add(Text{text: "TOTAL   4.50\n".to_string(), double_height: Some(true), align: Some(Align::Center), ..Default::default()}).unwrap();
+//! handler.add(Cut{cut_type: CutType::Feed}).unwrap();
+//!
+//! let preview = Preview::normal(handler.items(), &PreviewOptions{paper_width: PAPER_WIDTH_58MM});
+//! assert!(preview.to_svg().contains("TOTAL   4.50"));
+//! ```
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{barcodes::HRI, formatters::{Align, CutType, Font, Style}, normal::NormalElement, page::PageElement,
+    raster::PAPER_WIDTH_80MM, universal::{Barcode, Feed, Image, Symbol, Text}};
+
+/// Default line spacing, in dots
+const LINE_SPACING: u32 = 30;
+/// Default barcode height, in dots
+const BARCODE_HEIGHT: u32 = 162;
+/// Default barcode module width, in dots
+const BARCODE_MODULE: u32 = 3;
+/// Default symbol module width, in dots
+const SYMBOL_MODULE: u32 = 3;
+/// Paper fed ahead of a cut, in dots
+const CUT_FEED: u32 = 60;
+
+/// Options for rendering a preview.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviewOptions {
+    /// Printable width in dots, usually `raster::PAPER_WIDTH_80MM` or `raster::PAPER_WIDTH_58MM`.
+    pub paper_width: u32
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        PreviewOptions { paper_width: PAPER_WIDTH_80MM }
+    }
+}
+
+/// Something drawn on the preview. All positions and sizes are in dots.
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    /// A filled black rectangle
+    Fill { x: u32, y: u32, width: u32, height: u32 },
+    /// A run of text in cells of `cell_width` by `cell_height` dots
+    Text { x: u32, y: u32, text: String, cell_width: u32, cell_height: u32, bold: bool, underline: bool },
+    /// An image raster, one value per dot
+    Dots { x: u32, y: u32, width: u32, height: u32, dots: Vec<bool> },
+    /// A box standing in for something the preview doesn't draw
+    Placeholder { x: u32, y: u32, width: u32, height: u32, label: String },
+    /// The outline of a page mode print area
+    Area { x: u32, y: u32, width: u32, height: u32 },
+    /// A paper cut
+    Cut { y: u32 }
+}
+
+/// An approximate rendering of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preview {
+    width: u32,
+    height: u32,
+    shapes: Vec<Shape>
+}
+
+impl Preview {
+    /// Render the items of a normal mode document, such as `NormalBuilder::items()`.
+    pub fn normal(items: &[NormalElement], options: &PreviewOptions) -> Preview {
+        let mut layout = Layout::new(0, 0, options.paper_width);
+        for item in items {
+            match item {
+                NormalElement::Text(text) => layout.text(text),
+                NormalElement::Feed(feed) => layout.feed(feed),
+                NormalElement::Barcode(barcode) => layout.barcode(barcode),
+                NormalElement::Symbol(symbol) => layout.symbol(symbol),
+                NormalElement::Image(image) => layout.image(image),
+                NormalElement::Hline(line) => {
+                    layout.flush();
+                    let (x1, x2) = (line.x1.min(line.x2) as u32, line.x1.max(line.x2) as u32);
+                    layout.y += layout.line(x1, x2.saturating_sub(x1).max(1), line.style.as_ref(), false);
+                },
+                NormalElement::VlineBegin(begin) => {
+                    layout.flush();
+                    layout.vlines.insert(begin.x, layout.y);
+                },
+                NormalElement::VlineEnd(end) => {
+                    layout.flush();
+                    if let Some(start) = layout.vlines.remove(&end.x) {
+                        layout.line(end.x as u32, layout.y.saturating_sub(start).max(1), end.style.as_ref(), true);
+                    }
+                },
+                NormalElement::Cut(cut) => {
+                    layout.flush();
+                    if !matches!(cut.cut_type, CutType::NoFeed) {
+                        layout.y += CUT_FEED;
+                    }
+                    layout.shapes.push(Shape::Cut { y: layout.y });
+                },
+                NormalElement::Sound(_) | NormalElement::Command(_) | NormalElement::Pulse(_)
+                    | NormalElement::Recovery(_) | NormalElement::Reset(_) | NormalElement::Raw(_) => {}
+            }
+        }
+        layout.flush();
+        Preview { width: options.paper_width, height: layout.y.max(1), shapes: layout.shapes }
+    }
+
+    /// Render the items of a page mode document, such as `PageBuilder::items()`.
+    /// Items before the first `Area` are laid out across the full paper width.
+    pub fn page(items: &[PageElement], options: &PreviewOptions) -> Preview {
+        let mut layout = Layout::new(0, 0, options.paper_width);
+        let mut shapes = Vec::new();
+        let mut height = 0;
+        for item in items {
+            match item {
+                PageElement::Area(area) => {
+                    layout.flush();
+                    height = height.max(layout.y);
+                    shapes.append(&mut layout.shapes);
+                    let (x, y, width) = (area.x as u32, area.y as u32, area.width as u32);
+                    shapes.push(Shape::Area { x, y, width, height: area.height as u32 });
+                    height = height.max(y + area.height as u32);
+                    layout = Layout::new(x, y, width);
+                },
+                PageElement::Rectangle(rect) => {
+                    let (x1, x2) = (rect.x1.min(rect.x2) as u32, rect.x1.max(rect.x2) as u32);
+                    let (y1, y2) = (rect.y1.min(rect.y2) as u32, rect.y1.max(rect.y2) as u32);
+                    let (x1, x2) = (layout.left + x1, layout.left + x2);
+                    let (y1, y2) = (layout.top + y1, layout.top + y2);
+                    let thickness = line_thickness(rect.style.as_ref());
+                    for (x, y, width, height) in [(x1, y1, x2 - x1 + thickness, thickness), (x1, y2, x2 - x1 + thickness, thickness),
+                                                  (x1, y1, thickness, y2 - y1), (x2, y1, thickness, y2 - y1)] {
+                        layout.shapes.push(Shape::Fill { x, y, width, height });
+                    }
+                    height = height.max(y2 + thickness);
+                },
+                PageElement::Text(text) => layout.text(text),
+                PageElement::Feed(feed) => layout.feed(feed),
+                PageElement::Barcode(barcode) => layout.barcode(barcode),
+                PageElement::Symbol(symbol) => layout.symbol(symbol),
+                PageElement::Image(image) => layout.image(image),
+                PageElement::Sound(_) | PageElement::Command(_) | PageElement::Raw(_) => {}
+            }
+        }
+        layout.flush();
+        shapes.append(&mut layout.shapes);
+        Preview { width: options.paper_width, height: height.max(layout.y).max(1), shapes }
+    }
+
+    /// Width of the preview in dots.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the preview in dots.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Write the preview as an SVG document, one user unit per dot.
+    pub fn to_svg(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#, w = self.width, h = self.height);
+        let _ = write!(out, r#"<rect width="{}" height="{}" fill="white"/>"#, self.width, self.height);
+        for shape in &self.shapes {
+            let _ = match shape {
+                Shape::Fill { x, y, width, height } =>
+                    write!(out, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="black"/>"#, x, y, width, height),
+                Shape::Text { x, y, text, cell_width, cell_height, bold, underline } => write!(out,
+                    concat!(r#"<text x="{}" y="{}" font-family="monospace" font-size="{}" textLength="{}" lengthAdjust="spacingAndGlyphs""#,
+                        r#" xml:space="preserve"{}{}>{}</text>"#),
+                    x, y + cell_height * 4 / 5, cell_height, cell_width * text.chars().count() as u32,
+                    if *bold { r#" font-weight="bold""# } else { "" },
+                    if *underline { r#" text-decoration="underline""# } else { "" },
+                    quick_xml::escape::escape(text)),
+                Shape::Dots { x, y, width, dots, .. } => {
+                    let mut path = String::new();
+                    for (row, line) in dots.chunks(*width as usize).enumerate() {
+                        let mut col = 0;
+                        while col < line.len() {
+                            if !line[col] {
+                                col += 1;
+                                continue;
+                            }
+                            let start = col;
+                            while col < line.len() && line[col] {
+                                col += 1;
+                            }
+                            let _ = write!(path, "M{} {}h{}v1h-{}z", x + start as u32, y + row as u32, col - start, col - start);
+                        }
+                    }
+                    write!(out, r#"<path d="{}" fill="black"/>"#, path)
+                },
+                Shape::Placeholder { x, y, width, height, label } => write!(out,
+                    concat!(r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#d0d0d0" stroke="black"/>"##,
+                        r#"<text x="{}" y="{}" font-family="monospace" font-size="20" text-anchor="middle">{}</text>"#),
+                    x, y, width, height, x + width / 2, y + height / 2 + 7, quick_xml::escape::escape(label)),
+                Shape::Area { x, y, width, height } => write!(out,
+                    r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#4a90d9" stroke-dasharray="6 4"/>"##, x, y, width, height),
+                Shape::Cut { y } => write!(out,
+                    r##"<line x1="0" y1="{y}" x2="{}" y2="{y}" stroke="#d94a4a" stroke-width="2" stroke-dasharray="12 6"/>"##, self.width, y = y)
+            };
+        }
+        out.push_str("</svg>");
+        out
+    }
+
+    /// Draw the preview onto a grayscale bitmap, one pixel per dot.
+    /// Text is drawn as a shaded block per character, since no font is available.
+    #[cfg(feature = "image")]
+    pub fn to_luma(&self) -> image::GrayImage {
+        let mut img = image::GrayImage::from_pixel(self.width, self.height, image::Luma([255]));
+        let mut fill = |x: u32, y: u32, width: u32, height: u32, shade: u8| {
+            for py in y..(y + height).min(self.height) {
+                for px in x..(x + width).min(self.width) {
+                    img.put_pixel(px, py, image::Luma([shade]));
+                }
+            }
+        };
+        for shape in &self.shapes {
+            match shape {
+                Shape::Fill { x, y, width, height } => fill(*x, *y, *width, *height, 0),
+                Shape::Text { x, y, text, cell_width, cell_height, bold, underline } => {
+                    let shade = if *bold { 40 } else { 110 };
+                    for (idx, c) in text.chars().enumerate() {
+                        if !c.is_whitespace() {
+                            let cx = x + idx as u32 * cell_width;
+                            fill(cx + cell_width / 6, y + cell_height / 4, cell_width * 2 / 3, cell_height * 2 / 3, shade);
+                        }
+                    }
+                    if *underline {
+                        fill(*x, y + cell_height - 2, cell_width * text.chars().count() as u32, 2, 0);
+                    }
+                },
+                Shape::Dots { x, y, width, dots, .. } => {
+                    for (idx, dot) in dots.iter().enumerate() {
+                        if *dot {
+                            fill(x + idx as u32 % width, y + idx as u32 / width, 1, 1, 0);
+                        }
+                    }
+                },
+                Shape::Placeholder { x, y, width, height, .. } => {
+                    fill(*x, *y, *width, *height, 208);
+                    fill(*x, *y, *width, 1, 0);
+                    fill(*x, (y + height).saturating_sub(1), *width, 1, 0);
+                    fill(*x, *y, 1, *height, 0);
+                    fill((x + width).saturating_sub(1), *y, 1, *height, 0);
+                },
+                Shape::Area { x, y, width, height } => {
+                    for dash in (0..*width).step_by(10) {
+                        fill(x + dash, *y, 6, 1, 160);
+                        fill(x + dash, y + height.saturating_sub(1), 6, 1, 160);
+                    }
+                    for dash in (0..*height).step_by(10) {
+                        fill(*x, y + dash, 1, 6, 160);
+                        fill((x + width).saturating_sub(1), y + dash, 1, 6, 160);
+                    }
+                },
+                Shape::Cut { y } => {
+                    for dash in (0..self.width).step_by(18) {
+                        fill(dash, y.saturating_sub(1), 12, 2, 80);
+                    }
+                }
+            }
+        }
+        img
+    }
+
+    /// Write the preview as a PNG image, one pixel per dot. See `to_luma`.
+    #[cfg(feature = "image")]
+    pub fn to_png(&self) -> Result<Vec<u8>, crate::error::EPOSError> {
+        let mut out = std::io::Cursor::new(Vec::new());
+        self.to_luma().write_to(&mut out, image::ImageOutputFormat::Png)?;
+        Ok(out.into_inner())
+    }
+}
+
+/// Text settings. Like the printer, settings carry over from one `Text` to the next until they are changed.
+#[derive(Debug, Clone, Default)]
+struct TextState {
+    font: Option<Font>,
+    double_width: bool,
+    double_height: bool,
+    width: Option<u8>,
+    height: Option<u8>,
+    emph: bool,
+    underline: bool,
+    align: Option<Align>
+}
+
+impl TextState {
+    fn update(&mut self, text: &Text) {
+        if let Some(font) = &text.font { self.font = Some(font.clone()); }
+        if let Some(dw) = text.double_width { self.double_width = dw; }
+        if let Some(dh) = text.double_height { self.double_height = dh; }
+        if let Some(width) = text.width { self.width = Some(width.clamp(1, 8)); }
+        if let Some(height) = text.height { self.height = Some(height.clamp(1, 8)); }
+        if let Some(emph) = text.emph { self.emph = emph; }
+        if let Some(underline) = text.underline { self.underline = underline; }
+        if let Some(align) = &text.align { self.align = Some(align.clone()); }
+    }
+
+    /// Size of one character, in dots
+    fn cell(&self) -> (u32, u32) {
+        let (width, height) = match self.font {
+            Some(Font::FontB) => (9, 17),
+            _ => (12, 24)
+        };
+        let scale_x = self.width.map(u32::from).unwrap_or(if self.double_width { 2 } else { 1 });
+        let scale_y = self.height.map(u32::from).unwrap_or(if self.double_height { 2 } else { 1 });
+        (width * scale_x, height * scale_y)
+    }
+}
+
+/// A run of text on the current line
+struct Run {
+    text: String,
+    cell_width: u32,
+    cell_height: u32,
+    bold: bool,
+    underline: bool
+}
+
+/// Lays out items top to bottom within a print area.
+struct Layout {
+    left: u32,
+    top: u32,
+    width: u32,
+    y: u32,
+    line_spacing: u32,
+    state: TextState,
+    line: Vec<Run>,
+    /// start height of vertical lines that have not ended yet, by x position
+    vlines: HashMap<u16, u32>,
+    shapes: Vec<Shape>
+}
+
+impl Layout {
+    fn new(left: u32, top: u32, width: u32) -> Layout {
+        Layout { left, top, width, y: top, line_spacing: LINE_SPACING, state: TextState::default(), line: Vec::new(), vlines: HashMap::new(), shapes: Vec::new() }
+    }
+
+    fn line_width(&self) -> u32 {
+        self.line.iter().map(|run| run.cell_width * run.text.chars().count() as u32).sum()
+    }
+
+    /// Horizontal offset of something `width` dots wide, for the given alignment.
+    fn align(&self, align: Option<&Align>, width: u32) -> u32 {
+        let free = self.width.saturating_sub(width);
+        self.left + match align {
+            Some(Align::Center) => free / 2,
+            Some(Align::Right) => free,
+            _ => 0
+        }
+    }
+
+    fn text(&mut self, text: &Text) {
+        self.state.update(text);
+        let (cell_width, cell_height) = self.state.cell();
+        for c in text.text.chars() {
+            match c {
+                '\n' => self.newline(),
+                '\r' => {},
+                c => {
+                    if self.line_width() + cell_width > self.width && !self.line.is_empty() {
+                        self.newline();
+                    }
+                    let same_style = self.line.last().is_some_and(|run| run.cell_width == cell_width && run.cell_height == cell_height
+                        && run.bold == self.state.emph && run.underline == self.state.underline);
+                    if !same_style {
+                        self.line.push(Run { text: String::new(), cell_width, cell_height, bold: self.state.emph, underline: self.state.underline });
+                    }
+                    if let Some(run) = self.line.last_mut() {
+                        run.text.push(c);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Print the current line and move to the next one.
+    fn newline(&mut self) {
+        let height = self.line.iter().map(|run| run.cell_height).max().unwrap_or(0);
+        let mut x = self.align(self.state.align.as_ref(), self.line_width());
+        for run in self.line.drain(..) {
+            let width = run.cell_width * run.text.chars().count() as u32;
+            let y = self.y + height - run.cell_height;
+            self.shapes.push(Shape::Text { x, y, text: run.text, cell_width: run.cell_width, cell_height: run.cell_height,
+                bold: run.bold, underline: run.underline });
+            x += width;
+        }
+        self.y += height.max(self.line_spacing);
+    }
+
+    /// Print any text waiting for a line feed, as the printer does before other commands.
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            self.newline();
+        }
+    }
+
+    fn feed(&mut self, feed: &Feed) {
+        if let Some(spacing) = feed.linespc {
+            self.line_spacing = spacing as u32;
+        }
+        let pending = !self.line.is_empty();
+        self.flush();
+        if let Some(unit) = feed.unit {
+            self.y += unit as u32;
+        } else if let Some(lines) = feed.line {
+            self.y += self.line_spacing * lines as u32;
+        } else if feed.pos.is_some() {
+            self.y += CUT_FEED;
+        } else if feed.linespc.is_none() && !pending {
+            self.y += self.line_spacing;
+        }
+    }
+
+    /// Draw a horizontal or vertical line in the given style, returning its thickness.
+    fn line(&mut self, offset: u32, length: u32, style: Option<&Style>, vertical: bool) -> u32 {
+        let thickness = line_thickness(style);
+        let double = matches!(style, Some(Style::ThinDouble | Style::MediumDouble | Style::ThickDouble));
+        let (left, top) = (self.left, self.y);
+        let mut draw = |at: u32| self.shapes.push(if vertical {
+            Shape::Fill { x: left + offset + at, y: top.saturating_sub(length), width: thickness, height: length }
+        } else {
+            Shape::Fill { x: left + offset, y: top + at, width: length, height: thickness }
+        });
+        draw(0);
+        if double {
+            draw(thickness * 2);
+            return thickness * 3;
+        }
+        thickness
+    }
+
+    fn barcode(&mut self, barcode: &Barcode) {
+        self.flush();
+        let module = barcode.width().map(u32::from).unwrap_or(BARCODE_MODULE);
+        let height = barcode.height().map(u32::from).unwrap_or(BARCODE_HEIGHT);
+        // roughly 11 modules per character plus start and stop patterns
+        let width = ((barcode.text().chars().count() as u32 + 3) * 11 * module).min(self.width);
+        let x = self.align(barcode.align().or(self.state.align.as_ref()), width);
+        let hri = barcode.hri().filter(|hri| !matches!(hri, HRI::None));
+        if matches!(hri, Some(HRI::Above | HRI::Both)) {
+            self.hri(x, width, barcode.text());
+        }
+        self.shapes.push(Shape::Placeholder { x, y: self.y, width, height, label: format!("{} {}", barcode.barcode_type().name(), barcode.text()) });
+        self.y += height;
+        if matches!(hri, Some(HRI::Below | HRI::Both)) {
+            self.hri(x, width, barcode.text());
+        }
+    }
+
+    /// Print the human readable interpretation of a barcode, centered under it.
+    fn hri(&mut self, x: u32, width: u32, text: &str) {
+        let (cell_width, cell_height) = (12, 24);
+        let text_width = cell_width * text.chars().count() as u32;
+        let x = x + width.saturating_sub(text_width) / 2;
+        self.shapes.push(Shape::Text { x, y: self.y, text: text.to_string(), cell_width, cell_height, bold: false, underline: false });
+        self.y += cell_height;
+    }
+
+    fn symbol(&mut self, symbol: &Symbol) {
+        self.flush();
+        let module = symbol.width.map(u32::from).unwrap_or(SYMBOL_MODULE);
+        // enough modules for a small QR code or similar
+        let size = (module * 25).min(self.width);
+        let x = self.align(symbol.align.as_ref().or(self.state.align.as_ref()), size);
+        self.shapes.push(Shape::Placeholder { x, y: self.y, width: size, height: size, label: symbol.symbol_type.name().to_string() });
+        self.y += size;
+    }
+
+    fn image(&mut self, image: &Image) {
+        self.flush();
+        let (width, height) = (image.width().max(0) as u32, image.height().max(0) as u32);
+        if width == 0 || height == 0 {
+            // nothing to draw
+            self.y += height;
+            return;
+        }
+        let x = self.align(self.state.align.as_ref(), width);
+        let row_bytes = width.div_ceil(8) as usize;
+        match image.to_raster().filter(|raster| raster.len() == row_bytes * height as usize) {
+            Some(raster) => {
+                let dots = (0..height as usize).flat_map(|row| {
+                    let line = &raster[row * row_bytes..(row + 1) * row_bytes];
+                    (0..width as usize).map(move |col| line[col / 8] & (0x80 >> (col % 8)) != 0)
+                }).collect();
+                self.shapes.push(Shape::Dots { x, y: self.y, width, height, dots });
+            },
+            None => self.shapes.push(Shape::Placeholder { x, y: self.y, width, height, label: "image".to_string() })
+        }
+        self.y += height;
+    }
+}
+
+fn line_thickness(style: Option<&Style>) -> u32 {
+    match style {
+        Some(Style::Medium | Style::MediumDouble) => 2,
+        Some(Style::Thick | Style::ThickDouble) => 3,
+        _ => 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{formatters::{Align, CutType, Style}, normal::{Cut, Hline, NormalElement, VlineBegin, VlineEnd}, page::{Area, PageElement, Rectangle},
+        raster::PAPER_WIDTH_58MM, universal::{Feed, Image, Text}};
+
+    use super::{Preview, PreviewOptions, Shape};
+
+    fn text(text: &str) -> Text {
+        Text { text: text.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_text_layout() {
+        let items: Vec<NormalElement> = vec![
+            Text { align: Some(Align::Center), double_width: Some(true), ..text("AB\n") }.into(),
+            Text { double_width: Some(false), emph: Some(true), ..text("left") }.into(),
+            text(" right\n").into(),
+            Feed { line: Some(2), ..Default::default() }.into(),
+        ];
+        let preview = Preview::normal(&items, &PreviewOptions { paper_width: PAPER_WIDTH_58MM });
+        assert_eq!(preview.width(), 384);
+        assert_eq!(preview.height(), 30 * 4);
+        assert_eq!(preview.shapes[0], Shape::Text { x: (384 - 48) / 2, y: 0, text: "AB".to_string(), cell_width: 24, cell_height: 24, bold: false, underline: false });
+        // alignment and emphasis carry over to later text
+        assert_eq!(preview.shapes[1], Shape::Text { x: (384 - 120) / 2, y: 30, text: "left right".to_string(), cell_width: 12, cell_height: 24, bold: true, underline: false });
+    }
+
+    #[test]
+    fn test_wrap_and_lines() {
+        let items: Vec<NormalElement> = vec![
+            text(&"x".repeat(40)).into(),
+            Hline { x1: 0, x2: 200, style: Some(Style::ThinDouble) }.into(),
+            VlineBegin { x: 10, style: None }.into(),
+            text("a\n").into(),
+            VlineEnd { x: 10, style: None }.into(),
+            Cut { cut_type: CutType::Feed }.into(),
+        ];
+        let preview = Preview::normal(&items, &PreviewOptions::default());
+        assert!(matches!(&preview.shapes[0], Shape::Text { text, .. } if text.len() == 40));
+
+        let preview = Preview::normal(&items, &PreviewOptions { paper_width: 240 });
+        assert!(matches!(&preview.shapes[0], Shape::Text { text, .. } if text.len() == 20));
+        assert_eq!(preview.shapes[2], Shape::Fill { x: 0, y: 60, width: 200, height: 1 });
+        assert_eq!(preview.shapes[3], Shape::Fill { x: 0, y: 62, width: 200, height: 1 });
+        assert_eq!(preview.shapes[5], Shape::Fill { x: 10, y: 63, width: 1, height: 30 });
+        assert_eq!(preview.shapes[6], Shape::Cut { y: 93 + 60 });
+    }
+
+    #[test]
+    fn test_page_and_svg() {
+        let raster = [0b1010_0000u8, 0b0100_0000];
+        let items: Vec<PageElement> = vec![
+            Area { x: 10, y: 20, width: 300, height: 200 }.into(),
+            Rectangle { x1: 0, y1: 0, x2: 100, y2: 50, style: None }.into(),
+            text("Fish & Chips\n").into(),
+            Image::from_raster(&raster, 3, 2).unwrap().into(),
+        ];
+        let preview = Preview::page(&items, &PreviewOptions::default());
+        assert_eq!(preview.height(), 220);
+        assert_eq!(preview.shapes[1], Shape::Fill { x: 10, y: 20, width: 101, height: 1 });
+        assert!(matches!(&preview.shapes[5], Shape::Text { x: 10, y: 20, .. }));
+        assert_eq!(preview.shapes[6], Shape::Dots { x: 10, y: 50, width: 3, height: 2, dots: vec![true, false, true, false, true, false] });
+
+        let svg = preview.to_svg();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="576" height="220""#));
+        assert!(svg.contains(">Fish &amp; Chips</text>"));
+        assert!(svg.contains(r#"<path d="M10 50h1v1h-1zM12 50h1v1h-1zM11 51h1v1h-1z" fill="black"/>"#));
+        let mut reader = quick_xml::Reader::from_str(&svg);
+        while reader.read_event().unwrap() != quick_xml::events::Event::Eof {}
+    }
+
+    #[test]
+    fn test_empty_image() {
+        let empty: Image = quick_xml::de::from_str(r#"<image width="0" height="3">AAAA</image>"#).unwrap();
+        let items: Vec<NormalElement> = vec![Image::default().into(), empty.into(), text("a\n").into()];
+        let preview = Preview::normal(&items, &PreviewOptions::default());
+        assert!(matches!(preview.shapes[..], [Shape::Text { y: 3, .. }]));
+        assert!(preview.to_svg().ends_with("</text></svg>"));
+        #[cfg(feature = "image")]
+        assert_eq!(preview.to_luma().height(), 33);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_png() {
+        let items: Vec<NormalElement> = vec![Hline { x1: 0, x2: 10, style: Some(Style::Thick) }.into()];
+        let preview = Preview::normal(&items, &PreviewOptions { paper_width: 20 });
+        let img = preview.to_luma();
+        assert_eq!(img.dimensions(), (20, 3));
+        assert_eq!(img.get_pixel(0, 2).0, [0]);
+        assert_eq!(img.get_pixel(15, 0).0, [255]);
+        assert!(preview.to_png().unwrap().starts_with(b"\x89PNG"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Helpers for converting pixel buffers into the 1-bit raster used by `universal::Image`.
+
+/// Width of the printable area of 80 mm paper, in dots.
+pub const PAPER_WIDTH_80MM: u32 = 576;
+/// Width of the printable area of 58 mm paper, in dots.
+pub const PAPER_WIDTH_58MM: u32 = 384;
+
+/// Method used to reduce a grayscale image to black and white dots.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dither {
+    /// Floyd–Steinberg error diffusion. A good default for photos.
+    #[default]
+    FloydSteinberg,
+    /// Atkinson error diffusion. Keeps more contrast than Floyd–Steinberg, which suits logos.
+    Atkinson,
+    /// Pixels darker than the given level (0-255) are printed, everything else is left blank.
+    /// Best for line art and signatures.
+    Threshold(u8)
+}
+
+/// Options for converting a pixel buffer into an `Image`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImageOptions {
+    /// Scale the image to this width in dots, keeping its aspect ratio. Usually `PAPER_WIDTH_80MM` or `PAPER_WIDTH_58MM`.
+    /// If not set, the image is printed at one dot per pixel.
+    pub width: Option<u32>,
+    /// Dithering method
+    pub dither: Dither
+}
+
+/// Composite an RGBA buffer over a white background and convert it to 8-bit luma.
+pub(crate) fn rgba_to_luma(pixels: &[u8]) -> Vec<u8> {
+    pixels.chunks_exact(4).map(|px| {
+        let luma = 0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32;
+        let alpha = px[3] as f32 / 255.0;
+        (luma * alpha + 255.0 * (1.0 - alpha)).round() as u8
+    }).collect()
+}
+
+/// Scale a luma buffer to `new_width`, keeping the aspect ratio. Returns the new buffer and its height.
+/// Each destination pixel is the average of the source pixels it covers, or the nearest source pixel when enlarging.
+pub(crate) fn scale(pixels: &[u8], width: u32, height: u32, new_width: u32) -> (Vec<u8>, u32) {
+    if new_width == width {
+        return (pixels.to_vec(), height);
+    }
+    let new_height = ((height as u64 * new_width as u64 + width as u64 / 2) / width as u64).max(1) as u32;
+    let x_ratio = width as f32 / new_width as f32;
+    let y_ratio = height as f32 / new_height as f32;
+
+    let mut out = Vec::with_capacity((new_width * new_height) as usize);
+    for y in 0..new_height {
+        let y0 = (y as f32 * y_ratio) as u32;
+        let y1 = (((y + 1) as f32 * y_ratio) as u32).clamp(y0 + 1, height);
+        for x in 0..new_width {
+            let x0 = (x as f32 * x_ratio) as u32;
+            let x1 = (((x + 1) as f32 * x_ratio) as u32).clamp(x0 + 1, width);
+            let mut sum: u32 = 0;
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    sum += pixels[(sy * width + sx) as usize] as u32;
+                }
+            }
+            out.push((sum / ((y1 - y0) * (x1 - x0))) as u8);
+        }
+    }
+    (out, new_height)
+}
+
+/// Reduce a luma buffer to dots. Returns one value per pixel, `true` for a printed (black) dot.
+pub(crate) fn dither(pixels: &[u8], width: u32, height: u32, method: Dither) -> Vec<bool> {
+    let (width, height) = (width as usize, height as usize);
+    let diffusion: &[(isize, usize, f32)] = match method {
+        Dither::Threshold(level) => return pixels.iter().map(|px| *px < level).collect(),
+        Dither::FloydSteinberg => &[(1, 0, 7.0 / 16.0), (-1, 1, 3.0 / 16.0), (0, 1, 5.0 / 16.0), (1, 1, 1.0 / 16.0)],
+        Dither::Atkinson => &[(1, 0, 1.0 / 8.0), (2, 0, 1.0 / 8.0), (-1, 1, 1.0 / 8.0), (0, 1, 1.0 / 8.0), (1, 1, 1.0 / 8.0), (0, 2, 1.0 / 8.0)]
+    };
+
+    let mut levels: Vec<f32> = pixels.iter().map(|px| *px as f32).collect();
+    let mut out = vec![false; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            let old = levels[idx];
+            let black = old < 128.0;
+            out[idx] = black;
+            let error = old - if black { 0.0 } else { 255.0 };
+            for (dx, dy, weight) in diffusion {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx >= 0 && (nx as usize) < width && ny < height {
+                    levels[ny * width + nx as usize] += error * weight;
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Pack dots into rows of bytes, most significant bit first. Each row is padded to a whole byte.
+pub(crate) fn pack(dots: &[bool], width: u32, height: u32) -> Vec<u8> {
+    let row_bytes = width.div_ceil(8) as usize;
+    let mut out = vec![0u8; row_bytes * height as usize];
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            if dots[y * width as usize + x] {
+                out[y * row_bytes + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dither, pack, rgba_to_luma, scale, Dither};
+
+    #[test]
+    fn test_pack() {
+        let dots = [true, false, false, false, false, false, false, false, true,
+                    false, true, false, false, false, false, false, false, false];
+        assert_eq!(pack(&dots, 9, 2), vec![0x80, 0x80, 0x40, 0x00]);
+    }
+
+    #[test]
+    fn test_rgba_to_luma() {
+        assert_eq!(rgba_to_luma(&[0, 0, 0, 255, 0, 0, 0, 0, 255, 255, 255, 255]), vec![0, 255, 255]);
+    }
+
+    #[test]
+    fn test_scale() {
+        let (out, height) = scale(&[0, 255, 0, 255, 0, 255, 0, 255], 4, 2, 2);
+        assert_eq!(height, 1);
+        assert_eq!(out, vec![127, 127]);
+
+        let (out, height) = scale(&[0, 255], 2, 1, 4);
+        assert_eq!(height, 2);
+        assert_eq!(out, vec![0, 0, 255, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_dither() {
+        assert_eq!(dither(&[10, 200, 127], 3, 1, Dither::Threshold(128)), vec![true, false, true]);
+
+        let gray = vec![128u8; 64 * 64];
+        for method in [Dither::FloydSteinberg, Dither::Atkinson] {
+            let black = dither(&gray, 64, 64, method).iter().filter(|dot| **dot).count();
+            assert!((1800..2300).contains(&black), "{:?} printed {} dots", method, black);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Retrying requests that fail for transient reasons.
+//!
+//! A print is only sent again when the failure shows the printer never accepted the document: the connection could not be made,
+//! or the printer answered with a code that means it didn't print, such as `EX_TIMEOUT`. Failures that leave it unknown
+//! whether the document was printed, such as a connection dropped while waiting for the response, are not retried,
+//! so a receipt is not printed twice. If the document has a print job ID, the printer is asked about the job instead,
+//! and the document is sent again only if the printer has no record of it.
+//! Status queries don't print anything, and are retried after any network error or unexpected response.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use epos_rs::{retry::RetryPolicy, status::EposCode, Builder};
+//!
+//! let mut builder = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap();
+//! builder.set_retry_policy(RetryPolicy::new().max_attempts(5).initial_backoff(Duration::from_millis(500)).retry_code(EposCode::DeviceNotFound));
+//! ```
+
+use std::{collections::hash_map::RandomState, hash::{BuildHasher, Hasher}, io, time::Duration};
+
+use crate::{error::EPOSError, status::EposCode};
+
+/// How many times to send a request, and how long to wait between attempts.
+///
+/// The wait doubles after each attempt, starting at `initial_backoff` and capped at `max_backoff`.
+/// With jitter, each wait is picked at random between half and all of that, so that several clients don't retry in lockstep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    codes: Vec<EposCode>,
+    connect_errors: bool
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, waiting 200 ms and then 400 ms with jitter.
+    /// Connection errors and the codes `EX_TIMEOUT`, `EX_BADPORT` and `TooManyRequests` are retried.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+            codes: vec![EposCode::Timeout, EposCode::BadPort, EposCode::TooManyRequests],
+            connect_errors: true
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Start from the default policy.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    /// Send each request once. This is the policy of a new `Builder`.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy { max_attempts: 1, ..RetryPolicy::default() }
+    }
+
+    /// Send a request at most this many times, including the first attempt. Values below 1 are treated as 1.
+    pub fn max_attempts(self, attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts: attempts.max(1), ..self }
+    }
+
+    /// Wait this long before the first retry.
+    pub fn initial_backoff(self, backoff: Duration) -> RetryPolicy {
+        RetryPolicy { initial_backoff: backoff, ..self }
+    }
+
+    /// Never wait longer than this between attempts.
+    pub fn max_backoff(self, backoff: Duration) -> RetryPolicy {
+        RetryPolicy { max_backoff: backoff, ..self }
+    }
+
+    /// Randomize the wait between attempts.
+    pub fn jitter(self, jitter: bool) -> RetryPolicy {
+        RetryPolicy { jitter, ..self }
+    }
+
+    /// Also retry prints that fail with this response code.
+    /// Only add codes that the printer sends when it has not printed the document.
+    pub fn retry_code(mut self, code: EposCode) -> RetryPolicy {
+        if !self.codes.contains(&code) {
+            self.codes.push(code);
+        }
+        self
+    }
+
+    /// Don't retry prints that fail with this response code.
+    pub fn no_retry_code(mut self, code: &EposCode) -> RetryPolicy {
+        self.codes.retain(|retried| retried != code);
+        self
+    }
+
+    /// Whether to retry when the connection to the printer could not be made.
+    pub fn connect_errors(self, retry: bool) -> RetryPolicy {
+        RetryPolicy { connect_errors: retry, ..self }
+    }
+
+    /// The most times a request is sent.
+    pub fn attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns true if a print that failed with `error` can be sent again without risk of printing it twice.
+    pub fn retries(&self, error: &EPOSError) -> bool {
+        match error {
+            EPOSError::ResponseError { status } => self.codes.contains(&status.code),
+            EPOSError::NetworkError(err) => self.connect_errors && err.is_connect(),
+            EPOSError::IoError(err) => self.connect_errors && is_connect_error(err),
+            _ => false
+        }
+    }
+
+    /// Returns true if a status query that failed with `error` should be sent again.
+    pub(crate) fn retries_query(&self, error: &EPOSError) -> bool {
+        matches!(error, EPOSError::NetworkError(_) | EPOSError::IoError(_) | EPOSError::InvalidResponseError(_))
+    }
+
+    /// The wait before the given retry, counting from 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let backoff = self.initial_backoff.saturating_mul(factor).min(self.max_backoff);
+        if !self.jitter {
+            return backoff;
+        }
+        // a fresh `RandomState` is randomly seeded, which is enough to spread retries out
+        let random = RandomState::new().build_hasher().finish();
+        backoff.mul_f64(0.5 + (random as f64 / u64::MAX as f64) / 2.0)
+    }
+}
+
+/// Errors that happen before anything is sent to the printer.
+pub(crate) fn is_connect_error(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::ConnectionRefused | io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable
+        | io::ErrorKind::AddrNotAvailable | io::ErrorKind::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use crate::{error::EPOSError, status::{EposCode, Response}, transport::RecordingTransport, universal::Text, Builder};
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new().initial_backoff(Duration::from_millis(100)).max_backoff(Duration::from_millis(300)).jitter(false);
+        let waits: Vec<u128> = (1..=4).map(|retry| policy.backoff(retry).as_millis()).collect();
+        assert_eq!(waits, [100, 200, 300, 300]);
+
+        let policy = policy.jitter(true);
+        assert!((0..20).all(|_| (100..=200).contains(&policy.backoff(2).as_millis())));
+    }
+
+    #[test]
+    fn test_retries() {
+        let policy = RetryPolicy::new();
+        assert!(policy.retries(&io::Error::from(io::ErrorKind::ConnectionRefused).into()));
+        assert!(!policy.retries(&io::Error::from(io::ErrorKind::TimedOut).into()));
+        assert!(policy.retries(&EPOSError::ResponseError { status: Response::failure(EposCode::Timeout) }));
+        assert!(!policy.retries(&EPOSError::ResponseError { status: Response::failure(EposCode::CoverOpen) }));
+        assert!(!policy.retries(&EPOSError::ResponseError { status: Response::failure(EposCode::SchemaError) }));
+
+        let policy = policy.retry_code(EposCode::DeviceNotFound).no_retry_code(&EposCode::Timeout).connect_errors(false);
+        assert!(policy.retries(&EPOSError::ResponseError { status: Response::failure(EposCode::DeviceNotFound) }));
+        assert!(!policy.retries(&EPOSError::ResponseError { status: Response::failure(EposCode::Timeout) }));
+        assert!(!policy.retries(&io::Error::from(io::ErrorKind::ConnectionRefused).into()));
+    }
+
+    #[tokio::test]
+    async fn test_print_with_retries() {
+        let recorder = RecordingTransport::new();
+        let mut builder = Builder::with_transport(10000, "local_printer", recorder.clone());
+        builder.set_retry_policy(RetryPolicy::new().max_attempts(4).initial_backoff(Duration::from_millis(1)));
+        let mut handler = builder.normal();
+        handler.add(Text { text: "hello\n".to_string(), ..Default::default() }).unwrap();
+
+        recorder.push_error(io::Error::from(io::ErrorKind::ConnectionRefused).into());
+        recorder.push_response(Response::failure(EposCode::Timeout));
+        handler.print().await.unwrap();
+        assert_eq!(recorder.requests().len(), 3);
+
+        // the printer may have printed before the connection dropped
+        recorder.push_error(io::Error::from(io::ErrorKind::ConnectionReset).into());
+        assert!(handler.print().await.is_err());
+        assert_eq!(recorder.requests().len(), 4);
+
+        recorder.push_response(Response::failure(EposCode::CoverOpen));
+        assert!(matches!(handler.print().await, Err(EPOSError::ResponseError { status }) if status.code == EposCode::CoverOpen));
+        assert_eq!(recorder.requests().len(), 5);
+
+        for _ in 0..4 {
+            recorder.push_response(Response::failure(EposCode::Timeout));
+        }
+        assert!(matches!(handler.print().await, Err(EPOSError::ResponseError { status }) if status.code == EposCode::Timeout));
+        assert_eq!(recorder.requests().len(), 9);
+
+        recorder.push_error(io::Error::from(io::ErrorKind::ConnectionReset).into());
+        assert!(builder.status().await.unwrap().success);
+        assert_eq!(recorder.requests().len(), 11);
+    }
+
+    #[tokio::test]
+    async fn test_check_job_before_retry() {
+        let recorder = RecordingTransport::new();
+        let mut builder = Builder::with_transport(10000, "local_printer", recorder.clone());
+        builder.set_retry_policy(RetryPolicy::new().initial_backoff(Duration::from_millis(1)));
+        let mut handler = builder.normal();
+        handler.add(Text { text: "hello\n".to_string(), ..Default::default() }).unwrap();
+        handler.set_job_id("order-7").unwrap();
+
+        // the job never arrived, so it's sent again
+        recorder.push_error(io::Error::from(io::ErrorKind::ConnectionReset).into());
+        recorder.push_response(Response::failure(EposCode::JobNotFound));
+        handler.print().await.unwrap();
+        let requests = recorder.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(!requests[1].envelope.contains("<text>") && requests[2].envelope.contains("<text>"));
+
+        // the job was printed, so it isn't sent again
+        recorder.push_error(io::Error::from(io::ErrorKind::TimedOut).into());
+        let receipt = handler.print().await.unwrap();
+        assert_eq!(receipt.job_id.as_deref(), Some("order-7"));
+        assert_eq!(recorder.requests().len(), 5);
+
+        // still printing, so the original error is returned
+        recorder.push_error(io::Error::from(io::ErrorKind::TimedOut).into());
+        recorder.push_response(Response::failure(EposCode::Printing));
+        assert!(matches!(handler.print().await, Err(EPOSError::IoError(_))));
+        assert_eq!(recorder.requests().len(), 7);
+    }
+}
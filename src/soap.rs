@@ -1,20 +1,15 @@
 use log::{trace, debug};
+use quick_xml::{events::{BytesEnd, BytesStart, BytesText, Event}, Reader, Writer};
 use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use crate::{ status::Response, error::EPOSError};
+use crate::{ status::Response, error::EPOSError, universal::Layout, normal::NormalElement, page::PageElement, transport::Request};
 
-pub const ENDPOINT: &'static str = "/cgi-bin/epos/service.cgi"; 
+pub const ENDPOINT: &str = "/cgi-bin/epos/service.cgi"; 
 
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(rename = "s:Envelope")]
-pub struct SoapWrapper {
-    #[serde(rename = "@xmlns:s")]
-    pub ns: String,
-    #[serde(rename = "s:Body",  skip_serializing_if = "Option::is_none")]
-    pub body: Option<EposPrint>
-}
+const SOAP_NS: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+const EPOS_NS: &str = "http://www.epson-pos.com/schemas/2011/03/epos-print";
 
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename = "s:Envelope")]
@@ -31,73 +26,149 @@ pub struct ResponseBody {
     pub response: Response
 }
 
-
-
-#[derive(Deserialize, Serialize, Debug)]
-pub struct EposPrint {
-    #[serde(rename = "@xmlns")]
-    pub ns: String,
-
-    #[serde(rename = "$value")]
-    pub body: EnumBody
-}
-    
-#[derive(Deserialize, Serialize, Debug)]
-pub enum EnumBody {
-    #[serde(rename = "epos-print")]
+/// The contents of the `epos-print` element of a request.
+#[derive(Clone, Copy, Debug)]
+pub enum EnumBody<'a> {
+    /// A normal mode document
     NoPage {
-        #[serde(rename = "$value")]
-        body: String
+        layout: Option<&'a Layout>,
+        body: &'a [NormalElement]
     },
-    #[serde(rename = "epos-print")]
+    /// A page mode document
     Page {
-        #[serde(rename = "page")]
-        body: PageWrapper
+        layout: Option<&'a Layout>,
+        body: &'a [PageElement]
     },
-    #[serde(rename = "epos-print")]
-    Emtpy {}
+    /// No document, used to query the printer status
+    Empty
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct PageWrapper {
-    #[serde(rename = "$value")]
-    pub body: String
+/// Write an already-escaped XML fragment to the output as-is.
+fn write_fragment(writer: &mut Writer<Vec<u8>>, fragment: String) -> Result<(), EPOSError> {
+    writer.write_event(Event::Text(BytesText::from_escaped(fragment)))?;
+    Ok(())
 }
 
-pub async fn send_raw(body: EnumBody, devid: &str, timeout: i32, endpoint: &Url) -> Result<SoapRespWrapper, EPOSError> {
-    let full_request = SoapWrapper{
-        ns: String::from("http://schemas.xmlsoap.org/soap/envelope/"),
-        body: Some( EposPrint{
-            ns: String::from("http://www.epson-pos.com/schemas/2011/03/epos-print"), 
-            body: body
-        }),
-    };
+/// Compose the complete SOAP envelope for a request.
+/// Each item is serialized (and escaped) on its own, then written into the envelope without further processing.
+/// A print job ID is sent in the SOAP header.
+pub fn envelope(body: &EnumBody, job_id: Option<&str>) -> Result<String, EPOSError> {
+    let mut writer = Writer::new(Vec::new());
+    writer.write_event(Event::Start(BytesStart::new("s:Envelope").with_attributes([("xmlns:s", SOAP_NS)])))?;
+    if let Some(job_id) = job_id {
+        writer.write_event(Event::Start(BytesStart::new("s:Header")))?;
+        writer.write_event(Event::Start(BytesStart::new("parameter").with_attributes([("xmlns", EPOS_NS)])))?;
+        writer.write_event(Event::Start(BytesStart::new("printjobid")))?;
+        writer.write_event(Event::Text(BytesText::new(job_id)))?;
+        writer.write_event(Event::End(BytesEnd::new("printjobid")))?;
+        writer.write_event(Event::End(BytesEnd::new("parameter")))?;
+        writer.write_event(Event::End(BytesEnd::new("s:Header")))?;
+    }
+    writer.write_event(Event::Start(BytesStart::new("s:Body")))?;
+    writer.write_event(Event::Start(BytesStart::new("epos-print").with_attributes([("xmlns", EPOS_NS)])))?;
+
+    match body {
+        EnumBody::NoPage { layout, body } => {
+            if let Some(layout) = layout {
+                write_fragment(&mut writer, quick_xml::se::to_string(layout)?)?;
+            }
+            for item in body.iter() {
+                write_fragment(&mut writer, item.to_xml()?)?;
+            }
+        },
+        EnumBody::Page { layout, body } => {
+            if let Some(layout) = layout {
+                write_fragment(&mut writer, quick_xml::se::to_string(layout)?)?;
+            }
+            writer.write_event(Event::Start(BytesStart::new("page")))?;
+            for item in body.iter() {
+                write_fragment(&mut writer, item.to_xml()?)?;
+            }
+            writer.write_event(Event::End(BytesEnd::new("page")))?;
+        },
+        EnumBody::Empty => {}
+    }
+
+    writer.write_event(Event::End(BytesEnd::new("epos-print")))?;
+    writer.write_event(Event::End(BytesEnd::new("s:Body")))?;
+    writer.write_event(Event::End(BytesEnd::new("s:Envelope")))?;
 
-    let output = quick_xml::se::to_string(&full_request)?;
-    // we do this because we store the `self.build` as a string,  and the serialize option will escape the inner body
-    let fixed = quick_xml::escape::unescape(&output).unwrap();
-    trace!("Got complete XML: {}", fixed);
+    // the writer only ever receives valid UTF-8 strings
+    Ok(String::from_utf8(writer.into_inner()).expect("envelope is valid UTF-8"))
+}
+
+/// Return the print job ID in the SOAP header of an envelope.
+pub(crate) fn header_job_id(envelope: &str) -> Option<String> {
+    let mut reader = Reader::from_str(envelope);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) if start.local_name().as_ref() == b"printjobid" => {
+                return Some(reader.read_text(start.name()).ok()?.to_string());
+            },
+            Event::Start(start) if start.local_name().as_ref() == b"Body" => return None,
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
 
+/// Post a request to the ePOS-Print service and return its response, successful or not.
+pub async fn post(client: &reqwest::Client, endpoint: &Url, request: Request<'_>) -> Result<Response, EPOSError> {
+    let full_request = request.envelope()?;
+    trace!("Got complete XML: {}", full_request);
 
-    let client = reqwest::Client::new();
-    let params = [("devid", devid), ("timeout", &timeout.to_string())];
+    let params = [("devid", request.dev_id), ("timeout", &request.timeout.to_string())];
     let mut headers = HeaderMap::new();
     headers.insert(reqwest::header::CONTENT_TYPE,  "text/xml; charset=utf-8".parse()?);
     headers.insert(reqwest::header::IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT".parse()?);
-    let builder = client.post(endpoint.clone()).query(&params).headers(headers).body(fixed.to_string());
+    let builder = client.post(endpoint.clone()).query(&params).headers(headers).body(full_request);
 
     let resp = builder.send().await?.text().await?;
-    let formatted_resp: SoapRespWrapper = quick_xml::de::from_str(&resp)?;
+    // such as an error page while the printer's web server is starting
+    let formatted_resp: SoapRespWrapper = quick_xml::de::from_str(&resp).map_err(EPOSError::InvalidResponseError)?;
+    debug!("Got raw response: {:?}", formatted_resp);
 
-    Ok(formatted_resp)
+    Ok(formatted_resp.body.response)
 }
 
-pub async fn send(body: EnumBody, devid: &str, timeout: i32, endpoint: &Url) -> Result<(), EPOSError> {
-    let formatted_resp = send_raw(body, devid, timeout, endpoint).await?;
-    debug!("Got raw response: {:?}", formatted_resp);
-    if !formatted_resp.body.response.success {
-        return Err(EPOSError::ResponseError { status: formatted_resp.body.response })
+#[cfg(test)]
+mod tests {
+    use crate::{normal::{Cut, NormalElement}, page::{Area, PageElement}, universal::Text, formatters::CutType};
+
+    use super::{envelope, header_job_id, EnumBody};
+
+    #[test]
+    fn test_envelope_escapes_once() {
+        let body: Vec<NormalElement> = vec![
+            Text{text: String::from("Fish & Chips <large> \"2\"\n"), ..Default::default()}.into(),
+            Cut{cut_type: CutType::Feed}.into()
+        ];
+        let out = envelope(&EnumBody::NoPage { layout: None, body: &body }, None).unwrap();
+        assert_eq!(out, concat!(
+            r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body>"#,
+            r#"<epos-print xmlns="http://www.epson-pos.com/schemas/2011/03/epos-print">"#,
+            "<text>Fish &amp; Chips &lt;large&gt; &quot;2&quot;\n</text>",
+            r#"<cut type="feed"/>"#,
+            "</epos-print></s:Body></s:Envelope>"));
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn test_envelope_page() {
+        let body: Vec<PageElement> = vec![
+            Area{x: 0, y: 0, width: 100, height: 100}.into(),
+            Text{text: String::from("a&b"), ..Default::default()}.into()
+        ];
+        let out = envelope(&EnumBody::Page { layout: None, body: &body }, None).unwrap();
+        assert!(out.contains(r#"<page><area x="0" y="0" width="100" height="100"/><text>a&amp;b</text></page>"#));
+    }
+
+    #[test]
+    fn test_envelope_job_id() {
+        let out = envelope(&EnumBody::Empty, Some("order-1042")).unwrap();
+        assert_eq!(header_job_id(&out).as_deref(), Some("order-1042"));
+        assert_eq!(out, concat!(
+            r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Header>"#,
+            r#"<parameter xmlns="http://www.epson-pos.com/schemas/2011/03/epos-print"><printjobid>order-1042</printjobid></parameter>"#,
+            r#"</s:Header><s:Body><epos-print xmlns="http://www.epson-pos.com/schemas/2011/03/epos-print"></epos-print></s:Body></s:Envelope>"#));
+    }
+}
@@ -0,0 +1,519 @@
+//! A persistent print queue, so documents survive network failures, printer errors and process restarts.
+//!
+//! Submitted documents are written to a directory as SOAP envelopes, then printed in order by a background task.
+//! A document is only removed from the directory once the printer has printed it.
+//! If it can't be printed, it is retried with a growing delay until it is; documents that can never be printed,
+//! such as ones the printer rejects with `SchemaError`, are moved to the `failed` subdirectory so the rest of the queue isn't held up.
+//!
+//! Every spooled document gets a print job ID, so after a failure where it's unknown whether the printer received the document,
+//! or after a restart, the printer is asked about the job before the document is sent again. Transports without print job IDs,
+//! such as raw ESC/POS, can't be asked, so on those a document is sent again after such a failure, and may be printed twice.
+//!
+//! ```rust,no_run
+//! # tokio_test::block_on(async {
+//! use epos_rs::{spool::{SpoolOptions, Spooler}, universal::Text, Builder};
+//!
+//! let builder = Builder::new(10000, "local_printer", "http://192.168.1.194").unwrap();
+//! let spooler = Spooler::open("/var/spool/kitchen", builder.clone(), SpoolOptions::default()).await.unwrap();
+//!
+//! let mut ticket = builder.normal();
+//! ticket.add(Text{text: String::from("2x Pad Thai\n"), ..Default::default()}).unwrap();
+//! let job_id = spooler.submit_normal(&ticket).await.unwrap();
+//! # })
+//! ```
+//!
+//! One spooler should be used per printer and directory.
+
+use std::{collections::VecDeque, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use log::{debug, error, warn};
+use tokio::{io::AsyncWriteExt, sync::{watch, Notify}, task::JoinHandle};
+
+use crate::{error::EPOSError, parse::{self, Document, UnknownElements}, retry::RetryPolicy, send, soap::{self, header_job_id},
+    status::JobStatus, transport::{EnumBody, Request}, Builder, NormalBuilder, PageBuilder};
+
+/// Name of the subdirectory that documents that can never be printed are moved to
+pub const FAILED_DIR: &str = "failed";
+
+/// Options for a `Spooler`.
+#[derive(Clone, Debug)]
+pub struct SpoolOptions {
+    /// Delay before retrying a document the first time. The delay doubles with each retry of the same document.
+    pub initial_backoff: Duration,
+    /// The longest delay between retries
+    pub max_backoff: Duration
+}
+
+impl Default for SpoolOptions {
+    /// Start at 1 second, and back off to at most a minute.
+    fn default() -> Self {
+        SpoolOptions { initial_backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(60) }
+    }
+}
+
+/// A document waiting in the queue.
+#[derive(Clone, Debug)]
+struct Job {
+    seq: u64,
+    path: PathBuf,
+    /// Set once the printer may have received the document, so its job status must be checked before sending it again
+    maybe_sent: bool
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<Job>>,
+    notify: Notify,
+    pending: watch::Sender<usize>
+}
+
+impl Shared {
+    fn push(&self, job: Job) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.push_back(job);
+        self.pending.send_replace(queue.len());
+        self.notify.notify_one();
+    }
+}
+
+/// A persistent print queue for one printer. The background task stops when the `Spooler` is dropped,
+/// and documents that haven't been printed are picked up by the next `Spooler` opened on the same directory.
+pub struct Spooler {
+    dir: PathBuf,
+    shared: Arc<Shared>,
+    next_seq: Mutex<u64>,
+    handle: JoinHandle<()>
+}
+
+impl Drop for Spooler {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl Spooler {
+    /// Open the queue in `dir`, creating the directory if needed, and start printing any documents already in it.
+    /// Documents are printed through `builder`, with its transport and retry policy.
+    pub async fn open<P: Into<PathBuf>>(dir: P, builder: Builder, options: SpoolOptions) -> Result<Spooler, EPOSError> {
+        let dir = dir.into();
+        tokio::fs::create_dir_all(dir.join(FAILED_DIR)).await?;
+
+        let mut jobs = Vec::new();
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                // a submission that was interrupted before it was complete, and so was never accepted
+                Some("tmp") => tokio::fs::remove_file(&path).await?,
+                Some("xml") => if let Some(seq) = path.file_stem().and_then(|stem| stem.to_str()).and_then(seq_of) {
+                    jobs.push(Job { seq, path, maybe_sent: true });
+                },
+                _ => {}
+            }
+        }
+        jobs.sort_by_key(|job| job.seq);
+        debug!("recovered {} spooled documents from {}", jobs.len(), dir.display());
+
+        // keep counting past failed documents too, so their names are never reused
+        let mut last_seq = jobs.last().map(|job| job.seq).unwrap_or(0);
+        let mut failed = tokio::fs::read_dir(dir.join(FAILED_DIR)).await?;
+        while let Some(entry) = failed.next_entry().await? {
+            if let Some(seq) = entry.path().file_stem().and_then(|stem| stem.to_str()).and_then(seq_of) {
+                last_seq = last_seq.max(seq);
+            }
+        }
+        let next_seq = last_seq + 1;
+        let (pending, _) = watch::channel(jobs.len());
+        let shared = Arc::new(Shared { queue: Mutex::new(jobs.into()), notify: Notify::new(), pending });
+        let handle = tokio::spawn(drain(dir.clone(), builder, options, shared.clone()));
+
+        Ok(Spooler { dir, shared, next_seq: Mutex::new(next_seq), handle })
+    }
+
+    /// Check a normal mode document and add it to the queue. Returns the print job ID of the document,
+    /// which is the one set on the handler, or a new one if none was set.
+    /// When this returns, the document is safely on disk.
+    pub async fn submit_normal(&self, handler: &NormalBuilder) -> Result<String, EPOSError> {
+        handler.check()?;
+        self.submit(EnumBody::NoPage { layout: handler.layout.as_ref(), body: &handler.build }, handler.job_id.as_deref()).await
+    }
+
+    /// Check a page mode document and add it to the queue. See `submit_normal`.
+    pub async fn submit_page(&self, handler: &PageBuilder) -> Result<String, EPOSError> {
+        handler.check()?;
+        self.submit(EnumBody::Page { layout: handler.layout.as_ref(), body: &handler.build }, handler.job_id.as_deref()).await
+    }
+
+    async fn submit(&self, body: EnumBody<'_>, job_id: Option<&str>) -> Result<String, EPOSError> {
+        let seq = {
+            let mut next_seq = self.next_seq.lock().unwrap();
+            *next_seq += 1;
+            *next_seq - 1
+        };
+        let job_id = match job_id {
+            Some(job_id) => job_id.to_string(),
+            None => {
+                let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis()).unwrap_or_default();
+                format!("spool-{:x}-{:x}", millis, seq)
+            }
+        };
+        let envelope = soap::envelope(&body, Some(&job_id))?;
+
+        // write to a temporary file first, so a partly written document is never picked up
+        let path = self.dir.join(format!("{:016}.xml", seq));
+        let tmp = path.with_extension("tmp");
+        let mut file = tokio::fs::File::create(&tmp).await?;
+        file.write_all(envelope.as_bytes()).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        // the rename is only durable once the directory is synced too
+        #[cfg(unix)]
+        tokio::fs::File::open(&self.dir).await?.sync_all().await?;
+
+        self.shared.push(Job { seq, path, maybe_sent: false });
+        Ok(job_id)
+    }
+
+    /// The number of documents waiting to be printed.
+    pub fn pending(&self) -> usize {
+        *self.shared.pending.borrow()
+    }
+
+    /// Wait until every document in the queue has been printed or moved to the `failed` directory.
+    pub async fn wait_empty(&self) {
+        let mut pending = self.shared.pending.subscribe();
+        // the sender lives as long as `self`, so this only returns once the queue is empty
+        let _ = pending.wait_for(|pending| *pending == 0).await;
+    }
+
+    /// The directory the queue is kept in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// What became of a document.
+enum Outcome {
+    Printed,
+    Failed
+}
+
+/// Print queued documents in order, forever.
+async fn drain(dir: PathBuf, builder: Builder, options: SpoolOptions, shared: Arc<Shared>) {
+    // only the timing of this policy is used; whether the printer may have the document is decided by the builder's policy
+    let backoff = RetryPolicy::new().initial_backoff(options.initial_backoff).max_backoff(options.max_backoff);
+    loop {
+        let job = shared.queue.lock().unwrap().front().cloned();
+        let Some(mut job) = job else {
+            shared.notify.notified().await;
+            continue;
+        };
+
+        let mut retry = 1;
+        let outcome = loop {
+            match print_job(&builder, &mut job).await {
+                Ok(outcome) => break outcome,
+                Err(err) => {
+                    warn!("spooled document {} not printed, retrying: {}", job.path.display(), err);
+                    tokio::time::sleep(backoff.backoff(retry)).await;
+                    retry += 1;
+                }
+            }
+        };
+
+        let done = match outcome {
+            Outcome::Printed => tokio::fs::remove_file(&job.path).await,
+            Outcome::Failed => move_to_failed(&dir, &job).await
+        };
+        if let Err(err) = done {
+            error!("could not remove spooled document {}: {}", job.path.display(), err);
+        }
+        let mut queue = shared.queue.lock().unwrap();
+        queue.pop_front();
+        shared.pending.send_replace(queue.len());
+    }
+}
+
+/// The sequence number in a document's file name, such as `0000000000000042` or `0000000000000042-1`.
+fn seq_of(stem: &str) -> Option<u64> {
+    stem.split('-').next()?.parse().ok()
+}
+
+/// Move a document to the `failed` directory, never replacing a document already there.
+async fn move_to_failed(dir: &Path, job: &Job) -> std::io::Result<()> {
+    let failed = dir.join(FAILED_DIR);
+    let mut target = failed.join(format!("{:016}.xml", job.seq));
+    let mut copy = 0;
+    // unlike a rename, a hard link fails if the target exists
+    loop {
+        match tokio::fs::hard_link(&job.path, &target).await {
+            Ok(()) => break,
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                copy += 1;
+                target = failed.join(format!("{:016}-{}.xml", job.seq, copy));
+            },
+            Err(err) => return Err(err)
+        }
+    }
+    tokio::fs::remove_file(&job.path).await
+}
+
+/// Make one attempt at printing a document. Returns an error if it should be tried again.
+async fn print_job(builder: &Builder, job: &mut Job) -> Result<Outcome, EPOSError> {
+    let envelope = tokio::fs::read_to_string(&job.path).await?;
+    let Some(job_id) = header_job_id(&envelope) else {
+        error!("spooled document {} has no print job ID", job.path.display());
+        return Ok(Outcome::Failed);
+    };
+    let document = match parse::parse(&envelope, UnknownElements::Keep) {
+        Ok(document) => document,
+        Err(err) => {
+            error!("spooled document {} can't be read: {}", job.path.display(), err);
+            return Ok(Outcome::Failed);
+        }
+    };
+
+    let job_ids = builder.transport.supports_job_ids();
+    if job.maybe_sent && job_ids {
+        match builder.job_status(&job_id).await? {
+            JobStatus::Printed(_) => return Ok(Outcome::Printed),
+            JobStatus::Printing(resp) => return Err(EPOSError::ResponseError { status: resp }),
+            JobStatus::NotFound(_) | JobStatus::Failed(_) => {}
+        }
+    }
+
+    let body = match &document {
+        Document::Normal { layout, items } => EnumBody::NoPage { layout: layout.as_ref(), body: items },
+        Document::Page { layout, items } => EnumBody::Page { layout: layout.as_ref(), body: items }
+    };
+    let request = Request { body, dev_id: &builder.dev_id, timeout: builder.timeout, job_id: job_ids.then_some(job_id.as_str()) };
+    match send(builder.transport.as_ref(), &builder.retry, request).await {
+        Ok(_) => Ok(Outcome::Printed),
+        Err(err) if never_printable(&err) => {
+            error!("spooled document {} can never be printed: {}", job.path.display(), err);
+            Ok(Outcome::Failed)
+        },
+        Err(err) => {
+            // unless the printer can't have received it, ask about the job before sending it again.
+            // this must match the policy the document was sent with, or it could be sent again without asking
+            job.maybe_sent = job.maybe_sent || !builder.retry.retries(&err);
+            Err(err)
+        }
+    }
+}
+
+/// Returns true if a document that failed with `err` can never be printed: either it couldn't be sent at all,
+/// or the printer rejected it. Anything else, such as a reply that isn't SOAP, may succeed when sent again.
+fn never_printable(err: &EPOSError) -> bool {
+    match err {
+        EPOSError::ResponseError { .. } => err.is_programming_error(),
+        EPOSError::ValidationError(_) | EPOSError::DocumentError { .. } | EPOSError::UnsupportedError(_)
+            | EPOSError::SerializeError(_) | EPOSError::XmlError(_) => true,
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, path::PathBuf, time::Duration};
+
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use crate::{formatters::CutType, normal::{Cut, VlineBegin}, retry::RetryPolicy, status::{EposCode, Response}, testing::{MockPrinter, MockResponse},
+        transport::RecordingTransport, universal::Text, Builder};
+
+    use super::{SpoolOptions, Spooler, FAILED_DIR};
+
+    fn spool_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("epos-rs-spool-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn fast() -> SpoolOptions {
+        SpoolOptions { initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(10) }
+    }
+
+    fn spooled(dir: &PathBuf) -> usize {
+        std::fs::read_dir(dir).unwrap().filter(|entry| entry.as_ref().unwrap().path().is_file()).count()
+    }
+
+    #[tokio::test]
+    async fn test_spool_in_order() {
+        let dir = spool_dir("order");
+        let recorder = RecordingTransport::new();
+        recorder.push_error(io::Error::from(io::ErrorKind::ConnectionRefused).into());
+        recorder.push_response(Response::failure(EposCode::CoverOpen));
+        recorder.push_response(Response::failure(EposCode::CoverOpen));
+        let builder = Builder::with_transport(10000, "local_printer", recorder.clone());
+        let spooler = Spooler::open(&dir, builder.clone(), fast()).await.unwrap();
+
+        for (n, text) in ["one\n", "two\n"].iter().enumerate() {
+            let mut handler = builder.normal();
+            handler.add(Text { text: text.to_string(), ..Default::default() }).unwrap();
+            if n == 1 {
+                handler.set_job_id("ticket-2").unwrap();
+            }
+            let job_id = spooler.submit_normal(&handler).await.unwrap();
+            assert!(n == 0 || job_id == "ticket-2");
+        }
+        let mut unclosed = builder.normal();
+        unclosed.add(VlineBegin { x: 1, style: None }).unwrap();
+        assert!(spooler.submit_normal(&unclosed).await.is_err());
+
+        spooler.wait_empty().await;
+        assert_eq!(spooled(&dir), 0);
+
+        // refused, then cover open, then a status check because the printer had the document, then printed once the cover was closed
+        let requests = recorder.requests();
+        assert_eq!(requests.len(), 5);
+        assert!(requests[..4].iter().all(|req| req.job_id == requests[0].job_id));
+        assert!(requests[3].envelope.contains("<text>one\n</text>") && !requests[2].envelope.contains("<text>"));
+        assert_eq!(requests[4].job_id.as_deref(), Some("ticket-2"));
+        assert!(requests[4].envelope.contains("<text>two\n</text>"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spool_retry_policy() {
+        let dir = spool_dir("policy");
+        let recorder = RecordingTransport::new();
+        recorder.push_error(io::Error::from(io::ErrorKind::ConnectionRefused).into());
+        recorder.push_response(Response::failure(EposCode::JobNotFound));
+        let mut builder = Builder::with_transport(10000, "local_printer", recorder.clone());
+        builder.set_retry_policy(RetryPolicy::none().connect_errors(false));
+        let spooler = Spooler::open(&dir, builder.clone(), fast()).await.unwrap();
+        let mut handler = builder.normal();
+        handler.add(Text { text: "one\n".to_string(), ..Default::default() }).unwrap();
+        spooler.submit_normal(&handler).await.unwrap();
+        spooler.wait_empty().await;
+
+        // the builder doesn't treat refused connections as safe to send again, so neither does the spooler
+        let requests = recorder.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(!requests[1].envelope.contains("<text>") && requests[2].envelope.contains("<text>one\n</text>"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spool_recovery() {
+        let dir = spool_dir("recovery");
+        let unreachable = Builder::new(10000, "local_printer", "http://127.0.0.1:1").unwrap();
+        let slow = SpoolOptions { initial_backoff: Duration::from_secs(60), ..SpoolOptions::default() };
+        let spooler = Spooler::open(&dir, unreachable.clone(), slow).await.unwrap();
+        let mut ids = Vec::new();
+        for text in ["printed before the crash\n", "never received\n"] {
+            let mut handler = unreachable.normal();
+            handler.add(Text { text: text.to_string(), ..Default::default() }).unwrap();
+            handler.add(Cut { cut_type: CutType::Feed }).unwrap();
+            ids.push(spooler.submit_normal(&handler).await.unwrap());
+        }
+        assert_eq!(spooler.pending(), 2);
+        drop(spooler);
+        std::fs::write(dir.join("0000000000000003.tmp"), "<s:Envelope").unwrap();
+
+        let recorder = RecordingTransport::new();
+        recorder.push_response(Response { success: true, ..Default::default() });
+        recorder.push_response(Response::failure(EposCode::JobNotFound));
+        let spooler = Spooler::open(&dir, Builder::with_transport(10000, "local_printer", recorder.clone()), fast()).await.unwrap();
+        spooler.wait_empty().await;
+        assert_eq!(spooled(&dir), 0);
+
+        let requests = recorder.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests.iter().map(|req| req.job_id.clone().unwrap()).collect::<Vec<_>>(), [ids[0].clone(), ids[1].clone(), ids[1].clone()]);
+        assert!(requests[2].envelope.contains("<text>never received\n</text><cut type=\"feed\"/>"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spool_failed() {
+        let dir = spool_dir("failed");
+        let recorder = RecordingTransport::new();
+        recorder.push_response(Response::failure(EposCode::SchemaError));
+        let builder = Builder::with_transport(10000, "local_printer", recorder.clone());
+        let spooler = Spooler::open(&dir, builder.clone(), fast()).await.unwrap();
+        for text in ["bad\n", "good\n"] {
+            let mut handler = builder.normal();
+            handler.add(Text { text: text.to_string(), ..Default::default() }).unwrap();
+            spooler.submit_normal(&handler).await.unwrap();
+        }
+        spooler.wait_empty().await;
+
+        assert_eq!(recorder.requests().len(), 2);
+        assert_eq!(spooled(&dir), 0);
+        let failed: Vec<String> = std::fs::read_dir(dir.join(FAILED_DIR)).unwrap().map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap()).collect();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].contains("<text>bad\n</text>"));
+        drop(spooler);
+
+        // the queue is empty after a restart, but the failed document's name isn't reused
+        recorder.push_response(Response::failure(EposCode::SchemaError));
+        let spooler = Spooler::open(&dir, builder.clone(), fast()).await.unwrap();
+        let mut handler = builder.normal();
+        handler.add(Text { text: "also bad\n".to_string(), ..Default::default() }).unwrap();
+        spooler.submit_normal(&handler).await.unwrap();
+        spooler.wait_empty().await;
+        let mut failed: Vec<String> = std::fs::read_dir(dir.join(FAILED_DIR)).unwrap().map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap()).collect();
+        failed.sort();
+        assert_eq!(failed.len(), 2);
+        assert!(failed[1].contains("<text>also bad\n</text>"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spool_unexpected_reply() {
+        let dir = spool_dir("unexpected");
+        let printer = MockPrinter::start().await.unwrap();
+        printer.push_response(MockResponse::http(503, "<html><body>Service Unavailable</body></html>"));
+        printer.push_response(MockResponse::error("JobNotFound"));
+        let builder = Builder::new(10000, "local_printer", printer.url()).unwrap();
+        let spooler = Spooler::open(&dir, builder.clone(), fast()).await.unwrap();
+        let mut handler = builder.normal();
+        handler.add(Text { text: "2x Pad Thai\n".to_string(), ..Default::default() }).unwrap();
+        let job_id = spooler.submit_normal(&handler).await.unwrap();
+        spooler.wait_empty().await;
+
+        // the reply wasn't understood, so the printer is asked about the job, then the document is sent again
+        let requests = printer.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|req| req.print_job_id.as_deref() == Some(job_id.as_str())));
+        assert_eq!(requests[1].body, "");
+        assert_eq!(requests[2].body, "<text>2x Pad Thai\n</text>");
+        assert_eq!(std::fs::read_dir(dir.join(FAILED_DIR)).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spool_escpos() {
+        let dir = spool_dir("escpos");
+        let unreachable = Builder::new(1000, "local_printer", "tcp://127.0.0.1:1").unwrap();
+        let slow = SpoolOptions { initial_backoff: Duration::from_secs(60), ..SpoolOptions::default() };
+        let spooler = Spooler::open(&dir, unreachable.clone(), slow).await.unwrap();
+        let mut handler = unreachable.normal();
+        handler.add(Text { text: "2x Pad Thai\n".to_string(), ..Default::default() }).unwrap();
+        spooler.submit_normal(&handler).await.unwrap();
+        drop(spooler);
+
+        // the printer can't be asked about the job, so the recovered document is sent again
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+        let printer = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            while !received.ends_with(&[0x10, 0x04, 0x04]) {
+                let mut buf = [0u8; 1024];
+                let read = stream.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..read]);
+            }
+            stream.write_all(&[0x12; 4]).await.unwrap();
+            received
+        });
+        let spooler = Spooler::open(&dir, Builder::new(1000, "local_printer", url).unwrap(), fast()).await.unwrap();
+        spooler.wait_empty().await;
+
+        assert!(printer.await.unwrap().windows(12).any(|window| window == b"2x Pad Thai\n"));
+        assert_eq!(spooled(&dir), 0);
+        assert_eq!(std::fs::read_dir(dir.join(FAILED_DIR)).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,102 +2,469 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::error::ErrorKind;
+
 type StatusCode = u32;
 
-#[derive(Debug, Default, PartialEq, Serialize)]
-pub struct PrinterStatus {
+/// Response codes reported by the printer in `Response::code`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(from = "String", into = "String")]
+pub enum EposCode {
+    /// No code was reported, as in a successful response
+    #[default]
+    Empty,
+    /// `EPTR_AUTOMATICAL`: An automatically recoverable error occurred
+    AutoRecoverable,
+    /// `EPTR_BATTERY_LOW`: The battery has run out
+    BatteryLow,
+    /// `EPTR_COVER_OPEN`: The cover is open
+    CoverOpen,
+    /// `EPTR_CUTTER`: An autocutter error occurred
+    Cutter,
+    /// `EPTR_MECHANICAL`: A mechanical error occurred
+    Mechanical,
+    /// `EPTR_REC_EMPTY`: No paper in the roll paper end sensor
+    ReceiptEmpty,
+    /// `EPTR_UNRECOVERABLE`: An unrecoverable error occurred
+    Unrecoverable,
+    /// `SchemaError`: The request document contains a syntax error
+    SchemaError,
+    /// `DeviceNotFound`: The printer with the specified device ID does not exist
+    DeviceNotFound,
+    /// `PrintSystemError`: An error occurred in the printing system
+    PrintSystemError,
+    /// `EX_BADPORT`: An error was detected on the communication port
+    BadPort,
+    /// `EX_TIMEOUT`: The print timeout was exceeded
+    Timeout,
+    /// `EX_SPOOLER`: The print queue is full
+    Spooler,
+    /// `JobNotFound`: The specified print job ID does not exist
+    JobNotFound,
+    /// `Printing`: The print job is still being printed
+    Printing,
+    /// `TooManyRequests`: The number of print jobs sent exceeds the allowable limit
+    TooManyRequests,
+    /// `RequestEntityTooLarge`: The size of the print job data exceeds the printer's capacity
+    RequestEntityTooLarge,
+    /// Any other code
+    Other(String)
+}
+
+impl EposCode {
+    /// The code, as sent by the printer.
+    pub fn as_str(&self) -> &str {
+        match self {
+            EposCode::Empty => "",
+            EposCode::AutoRecoverable => "EPTR_AUTOMATICAL",
+            EposCode::BatteryLow => "EPTR_BATTERY_LOW",
+            EposCode::CoverOpen => "EPTR_COVER_OPEN",
+            EposCode::Cutter => "EPTR_CUTTER",
+            EposCode::Mechanical => "EPTR_MECHANICAL",
+            EposCode::ReceiptEmpty => "EPTR_REC_EMPTY",
+            EposCode::Unrecoverable => "EPTR_UNRECOVERABLE",
+            EposCode::SchemaError => "SchemaError",
+            EposCode::DeviceNotFound => "DeviceNotFound",
+            EposCode::PrintSystemError => "PrintSystemError",
+            EposCode::BadPort => "EX_BADPORT",
+            EposCode::Timeout => "EX_TIMEOUT",
+            EposCode::Spooler => "EX_SPOOLER",
+            EposCode::JobNotFound => "JobNotFound",
+            EposCode::Printing => "Printing",
+            EposCode::TooManyRequests => "TooManyRequests",
+            EposCode::RequestEntityTooLarge => "RequestEntityTooLarge",
+            EposCode::Other(code) => code
+        }
+    }
+
+    /// Classify a failure with this code.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            EposCode::AutoRecoverable | EposCode::PrintSystemError | EposCode::BadPort | EposCode::Timeout
+                | EposCode::Printing | EposCode::TooManyRequests => ErrorKind::Retryable,
+            EposCode::SchemaError | EposCode::JobNotFound | EposCode::RequestEntityTooLarge => ErrorKind::Programming,
+            EposCode::Empty | EposCode::BatteryLow | EposCode::CoverOpen | EposCode::Cutter | EposCode::Mechanical
+                | EposCode::ReceiptEmpty | EposCode::Unrecoverable | EposCode::DeviceNotFound | EposCode::Spooler
+                | EposCode::Other(_) => ErrorKind::Operator
+        }
+    }
+}
+
+impl From<String> for EposCode {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "" => EposCode::Empty,
+            "EPTR_AUTOMATICAL" => EposCode::AutoRecoverable,
+            "EPTR_BATTERY_LOW" => EposCode::BatteryLow,
+            "EPTR_COVER_OPEN" => EposCode::CoverOpen,
+            "EPTR_CUTTER" => EposCode::Cutter,
+            "EPTR_MECHANICAL" => EposCode::Mechanical,
+            "EPTR_REC_EMPTY" => EposCode::ReceiptEmpty,
+            "EPTR_UNRECOVERABLE" => EposCode::Unrecoverable,
+            "SchemaError" => EposCode::SchemaError,
+            "DeviceNotFound" => EposCode::DeviceNotFound,
+            "PrintSystemError" => EposCode::PrintSystemError,
+            "EX_BADPORT" => EposCode::BadPort,
+            "EX_TIMEOUT" => EposCode::Timeout,
+            "EX_SPOOLER" => EposCode::Spooler,
+            "JobNotFound" => EposCode::JobNotFound,
+            "Printing" => EposCode::Printing,
+            "TooManyRequests" => EposCode::TooManyRequests,
+            "RequestEntityTooLarge" => EposCode::RequestEntityTooLarge,
+            _ => EposCode::Other(value)
+        }
+    }
+}
+
+impl From<EposCode> for String {
+    fn from(value: EposCode) -> Self {
+        value.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for EposCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Decoded status bitmask reported by the printer, with a bitflags-style API.
+///
+/// The raw value is kept as-is, so converting back to a `u32` is lossless, including undocumented bits.
+/// Some bits have a different meaning depending on the printer model, these are exposed as aliases:
+/// `BATTERY_OFFLINE` shares a bit with `DRAWER_KICK` (mobile printers have no drawer connector),
+/// and `WAIT_REMOVE_LABEL` shares a bit with `BUZZER` (label printers have no buzzer).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PrinterStatus(StatusCode);
+
+impl PrinterStatus {
     /// No response from the TM printer
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub no_response: bool,
+    pub const NO_RESPONSE: PrinterStatus = PrinterStatus(0x00000001);
     /// Printing is successfully completed
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub success: bool,
+    pub const PRINT_SUCCESS: PrinterStatus = PrinterStatus(0x00000002);
     /// Status of the 3rd pin of the drawer kick-out connector = "H"
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub drawer_kick_out_connector: bool,
-    /// Off line status from remaining battery
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub battery_offline_status: bool,
+    pub const DRAWER_KICK: PrinterStatus = PrinterStatus(0x00000004);
+    /// Off line status from remaining battery (mobile printers only, same bit as `DRAWER_KICK`)
+    pub const BATTERY_OFFLINE: PrinterStatus = PrinterStatus(0x00000004);
     /// Offline
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub offline: bool,
+    pub const OFFLINE: PrinterStatus = PrinterStatus(0x00000008);
+    /// Reserved. TM printers always report this bit as set
+    pub const RESERVED_FIXED: PrinterStatus = PrinterStatus(0x00000010);
     /// The cover is open
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub cover_open: bool,
+    pub const COVER_OPEN: PrinterStatus = PrinterStatus(0x00000020);
     /// Paper is being fed by a paper feed switch operation
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub paper_feed_operation: bool,
+    pub const PAPER_FEED: PrinterStatus = PrinterStatus(0x00000040);
     /// Waiting to be brought back online
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub waiting_online: bool,
+    pub const WAIT_ONLINE: PrinterStatus = PrinterStatus(0x00000100);
     /// The paper feed switch is being pressed
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub paper_feed_switch_pressed: bool,
+    pub const PANEL_SWITCH: PrinterStatus = PrinterStatus(0x00000200);
     /// A mechanical error occurred
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub mechanical_error: bool,
+    pub const MECHANICAL_ERROR: PrinterStatus = PrinterStatus(0x00000400);
     /// An autocutter error occurred
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub autocutter_error: bool,
+    pub const AUTOCUTTER_ERROR: PrinterStatus = PrinterStatus(0x00000800);
     /// An unrecoverable error occurred
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub unrecoverable: bool,
+    pub const UNRECOVERABLE_ERROR: PrinterStatus = PrinterStatus(0x00002000);
     /// Automatically recoverable error occurred
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub recoverable: bool,
+    pub const AUTO_RECOVERABLE_ERROR: PrinterStatus = PrinterStatus(0x00004000);
+    /// No paper in roll paper near end sensor, first bit of the pair
+    pub const RECEIPT_NEAR_END_FIRST: PrinterStatus = PrinterStatus(0x00010000);
     /// No paper in roll paper near end sensor
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub no_paper_roll_near_end_sensor: bool,
-    /// No paper in roll paper ens sensor
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub no_paper_in_roll_paper_end_sensor: bool,
+    pub const RECEIPT_NEAR_END: PrinterStatus = PrinterStatus(0x00020000);
+    /// No paper in roll paper end sensor, first bit of the pair
+    pub const RECEIPT_END_FIRST: PrinterStatus = PrinterStatus(0x00040000);
+    /// No paper in roll paper end sensor
+    pub const RECEIPT_END: PrinterStatus = PrinterStatus(0x00080000);
     /// A buzzer is on (only for applicable devices)
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub buzzer_on: bool,
-    /// Waiting period for removal of label (only for applicable devices)
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub label_wait_removal: bool,
+    pub const BUZZER: PrinterStatus = PrinterStatus(0x01000000);
+    /// Waiting period for removal of label (label printers only, same bit as `BUZZER`)
+    pub const WAIT_REMOVE_LABEL: PrinterStatus = PrinterStatus(0x01000000);
     /// No paper in label peeling sensor (only for applicable devices)
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub no_paper_in_peel_sensor: bool,
-    /// The spooler has stopped (unused)
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
-    pub spooler_stopped: bool,
+    pub const NO_LABEL: PrinterStatus = PrinterStatus(0x04000000);
+    /// The spooler has stopped
+    pub const SPOOLER_STOPPED: PrinterStatus = PrinterStatus(0x80000000);
+
+    /// All documented flags, without aliases, with their constant name and a readable description.
+    const FLAGS: &'static [(&'static str, &'static str, PrinterStatus)] = &[
+        ("NO_RESPONSE", "no response", PrinterStatus::NO_RESPONSE),
+        ("PRINT_SUCCESS", "print succeeded", PrinterStatus::PRINT_SUCCESS),
+        ("DRAWER_KICK", "drawer kick-out connector high", PrinterStatus::DRAWER_KICK),
+        ("OFFLINE", "offline", PrinterStatus::OFFLINE),
+        ("RESERVED_FIXED", "", PrinterStatus::RESERVED_FIXED),
+        ("COVER_OPEN", "cover open", PrinterStatus::COVER_OPEN),
+        ("PAPER_FEED", "feeding paper", PrinterStatus::PAPER_FEED),
+        ("WAIT_ONLINE", "waiting to come online", PrinterStatus::WAIT_ONLINE),
+        ("PANEL_SWITCH", "feed switch pressed", PrinterStatus::PANEL_SWITCH),
+        ("MECHANICAL_ERROR", "mechanical error", PrinterStatus::MECHANICAL_ERROR),
+        ("AUTOCUTTER_ERROR", "autocutter error", PrinterStatus::AUTOCUTTER_ERROR),
+        ("UNRECOVERABLE_ERROR", "unrecoverable error", PrinterStatus::UNRECOVERABLE_ERROR),
+        ("AUTO_RECOVERABLE_ERROR", "automatically recoverable error", PrinterStatus::AUTO_RECOVERABLE_ERROR),
+        ("RECEIPT_NEAR_END_FIRST", "paper near end", PrinterStatus::RECEIPT_NEAR_END_FIRST),
+        ("RECEIPT_NEAR_END", "paper near end", PrinterStatus::RECEIPT_NEAR_END),
+        ("RECEIPT_END_FIRST", "paper out", PrinterStatus::RECEIPT_END_FIRST),
+        ("RECEIPT_END", "paper out", PrinterStatus::RECEIPT_END),
+        ("BUZZER", "buzzer on", PrinterStatus::BUZZER),
+        ("NO_LABEL", "no label at peeler", PrinterStatus::NO_LABEL),
+        ("SPOOLER_STOPPED", "spooler stopped", PrinterStatus::SPOOLER_STOPPED),
+    ];
+
+    /// A status with no bits set.
+    pub const fn empty() -> PrinterStatus {
+        PrinterStatus(0)
+    }
+
+    /// A status with every documented bit set.
+    pub fn all() -> PrinterStatus {
+        PrinterStatus::FLAGS.iter().fold(PrinterStatus::empty(), |acc, (_, _, flag)| acc | *flag)
+    }
+
+    /// Create a status from a raw bitmask, keeping undocumented bits.
+    pub const fn from_bits_retain(bits: StatusCode) -> PrinterStatus {
+        PrinterStatus(bits)
+    }
+
+    /// The raw bitmask.
+    pub const fn bits(&self) -> StatusCode {
+        self.0
+    }
+
+    /// Returns true if no bits are set.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if all bits of `other` are set.
+    pub const fn contains(&self, other: PrinterStatus) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if any bit of `other` is set.
+    pub const fn intersects(&self, other: PrinterStatus) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Set the bits of `other`.
+    pub fn insert(&mut self, other: PrinterStatus) {
+        self.0 |= other.0;
+    }
+
+    /// Clear the bits of `other`.
+    pub fn remove(&mut self, other: PrinterStatus) {
+        self.0 &= !other.0;
+    }
+
+    /// Bits set in either status.
+    pub const fn union(self, other: PrinterStatus) -> PrinterStatus {
+        PrinterStatus(self.0 | other.0)
+    }
+
+    /// Bits set in both statuses.
+    pub const fn intersection(self, other: PrinterStatus) -> PrinterStatus {
+        PrinterStatus(self.0 & other.0)
+    }
+
+    /// Bits set in this status but not in `other`.
+    pub const fn difference(self, other: PrinterStatus) -> PrinterStatus {
+        PrinterStatus(self.0 & !other.0)
+    }
+
+    /// Iterate over the documented flags that are set, as (name, flag) pairs. Aliases are not repeated.
+    pub fn iter_names(&self) -> impl Iterator<Item = (&'static str, PrinterStatus)> + '_ {
+        PrinterStatus::FLAGS.iter().filter(|(_, _, flag)| self.contains(*flag)).map(|(name, _, flag)| (*name, *flag))
+    }
+
+    /// Returns true if either roll paper near end bit is set.
+    pub const fn paper_near_end(&self) -> bool {
+        self.intersects(PrinterStatus::RECEIPT_NEAR_END_FIRST.union(PrinterStatus::RECEIPT_NEAR_END))
+    }
+
+    /// Returns true if either roll paper end bit is set.
+    pub const fn paper_end(&self) -> bool {
+        self.intersects(PrinterStatus::RECEIPT_END_FIRST.union(PrinterStatus::RECEIPT_END))
+    }
+
+    /// Classify how urgently the printer needs attention.
+    pub fn severity(&self) -> Severity {
+        if self.contains(PrinterStatus::UNRECOVERABLE_ERROR) {
+            Severity::Fatal
+        } else if self.intersects(PrinterStatus::NO_RESPONSE | PrinterStatus::OFFLINE | PrinterStatus::COVER_OPEN
+            | PrinterStatus::MECHANICAL_ERROR | PrinterStatus::AUTOCUTTER_ERROR | PrinterStatus::SPOOLER_STOPPED) || self.paper_end() {
+            Severity::Error
+        } else if self.intersects(PrinterStatus::WAIT_ONLINE | PrinterStatus::AUTO_RECOVERABLE_ERROR) || self.paper_near_end() {
+            Severity::Warning
+        } else {
+            Severity::Ok
+        }
+    }
 }
 
-impl std::fmt::Display for PrinterStatus {
+/// How urgently a printer needs attention, from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Severity {
+    /// The printer is ready
+    Ok,
+    /// The printer works, but will need attention soon, such as when the paper is running low
+    Warning,
+    /// The printer can't print until the error is cleared
+    Error,
+    /// The printer can't recover without being serviced
+    Fatal
+}
+
+impl std::fmt::Display for Severity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let str = serde_json::to_string(self).unwrap();
+        let str = match self {
+            Severity::Ok => "ok",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Fatal => "fatal",
+        };
         write!(f, "{}", str)
     }
 }
 
+impl std::ops::BitOr for PrinterStatus {
+    type Output = PrinterStatus;
+    fn bitor(self, rhs: PrinterStatus) -> PrinterStatus {
+        self.union(rhs)
+    }
+}
+
+impl std::ops::BitOrAssign for PrinterStatus {
+    fn bitor_assign(&mut self, rhs: PrinterStatus) {
+        self.insert(rhs);
+    }
+}
+
+impl std::ops::BitAnd for PrinterStatus {
+    type Output = PrinterStatus;
+    fn bitand(self, rhs: PrinterStatus) -> PrinterStatus {
+        self.intersection(rhs)
+    }
+}
+
+impl std::ops::BitAndAssign for PrinterStatus {
+    fn bitand_assign(&mut self, rhs: PrinterStatus) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl std::ops::BitXor for PrinterStatus {
+    type Output = PrinterStatus;
+    fn bitxor(self, rhs: PrinterStatus) -> PrinterStatus {
+        PrinterStatus(self.0 ^ rhs.0)
+    }
+}
+
+impl std::ops::Sub for PrinterStatus {
+    type Output = PrinterStatus;
+    fn sub(self, rhs: PrinterStatus) -> PrinterStatus {
+        self.difference(rhs)
+    }
+}
+
+impl std::ops::Not for PrinterStatus {
+    type Output = PrinterStatus;
+    fn not(self) -> PrinterStatus {
+        PrinterStatus(!self.0)
+    }
+}
+
+impl std::fmt::Debug for PrinterStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<&str> = self.iter_names().map(|(name, _)| name).collect();
+        write!(f, "PrinterStatus({:#010x}: {})", self.0, names.join(" | "))
+    }
+}
+
+/// A readable summary of the status, such as `error: offline, cover open`.
+impl std::fmt::Display for PrinterStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut descriptions: Vec<&str> = Vec::new();
+        for (_, description, flag) in PrinterStatus::FLAGS {
+            if self.contains(*flag) && !description.is_empty() && !descriptions.contains(description) {
+                descriptions.push(description);
+            }
+        }
+        if descriptions.is_empty() {
+            write!(f, "{}", self.severity())
+        } else {
+            write!(f, "{}: {}", self.severity(), descriptions.join(", "))
+        }
+    }
+}
+
 impl From<StatusCode> for PrinterStatus {
     fn from(value: StatusCode) -> Self {
-        PrinterStatus {
-            no_response: (value & 0x00000001) != 0,
-            success: (value & 0x00000002) != 0,
-            drawer_kick_out_connector: (value & 0x00000004) != 0,
-            battery_offline_status: (value & 0x00000004) != 0,
-            offline: (value & 0x00000008) != 0,
-            cover_open: (value & 0x00000020) != 0,
-            paper_feed_operation: (value & 0x00000040) != 0,
-            waiting_online: (value & 0x00000100) != 0,
-            paper_feed_switch_pressed: (value & 0x00000200) != 0,
-            mechanical_error: (value & 0x00000400) != 0,
-            autocutter_error: (value & 0x00000800) != 0,
-            unrecoverable: (value & 0x00002000) != 0,
-            recoverable: (value & 0x00004000) != 0,
-            no_paper_roll_near_end_sensor: (value & 0x00020000) != 0,
-            no_paper_in_roll_paper_end_sensor: (value & 0x00080000) != 0,
-            buzzer_on: (value & 0x01000000) != 0,
-            label_wait_removal: (value & 0x01000000) != 0,
-            no_paper_in_peel_sensor: (value & 0x40000000) != 0,
-            spooler_stopped: (value & 0x80000000) != 0,
-        }
+        PrinterStatus(value)
     }
 }
 
+impl From<PrinterStatus> for StatusCode {
+    fn from(value: PrinterStatus) -> Self {
+        value.0
+    }
+}
+
+
+/// Power source of a mobile printer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerSource {
+    /// Running from the AC adapter
+    Ac,
+    /// Running from the battery
+    Battery
+}
+
+/// Remaining battery charge of a mobile printer, from least to most charged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ChargeLevel {
+    Critical,
+    Low,
+    Medium,
+    High,
+    Full
+}
+
+/// Decoded battery status of a mobile printer.
+///
+/// The ePOS `battery` field holds the power source in its upper byte (`0x30` for the AC adapter, `0x31` for the battery),
+/// and the remaining charge in its lower byte, from `0x30` (empty) to `0x36` (full).
+/// Printers without a battery report `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatteryStatus {
+    pub source: PowerSource,
+    pub level: ChargeLevel
+}
+
+impl BatteryStatus {
+    /// Decode the ePOS `battery` field. Returns `None` if the printer has no battery, or the value is not recognized.
+    pub fn from_code(code: u32) -> Option<BatteryStatus> {
+        let source = match (code >> 8) & 0xff {
+            0x30 => PowerSource::Ac,
+            0x31 => PowerSource::Battery,
+            _ => return None
+        };
+        let level = match code & 0xff {
+            0x30 | 0x31 => ChargeLevel::Critical,
+            0x32 => ChargeLevel::Low,
+            0x33 => ChargeLevel::Medium,
+            0x34 | 0x35 => ChargeLevel::High,
+            0x36 => ChargeLevel::Full,
+            _ => return None
+        };
+        if code >> 16 != 0 {
+            return None;
+        }
+        Some(BatteryStatus { source, level })
+    }
+
+    /// Returns true if the printer doesn't have enough charge to get through a shift away from the AC adapter.
+    /// The power source is ignored, since a printer that is charging now will be unplugged for the shift.
+    pub fn should_take_out_of_service(&self) -> bool {
+        self.level <= ChargeLevel::Low
+    }
+}
 
 #[derive(Deserialize, Serialize, Debug, Default)]
 /// A status response from ePOS's SOAP API
@@ -109,16 +476,73 @@ pub struct Response {
     /// Was the last print successful?
     pub success: bool,
     #[serde(rename = "@code")]
-    /// String code, in cases of failure
-    pub code: String,
+    /// Response code, in cases of failure
+    pub code: EposCode,
     #[serde(rename = "@status")]
     /// A 32-bit bitmask of current status bits set
     /// Convert to a more readable struct by
     /// `let out: PrinterStatus = status.into()`
     pub status: StatusCode,
-    /// Battery status code
+    /// Battery status code.
+    /// Convert to a more readable struct with `status.battery_status()`
     #[serde(rename = "@battery")]
-    pub battery: u32
+    pub battery: u32,
+    /// The print job ID sent with the request, if any
+    #[serde(rename = "@printjobid", default, skip_serializing_if = "Option::is_none")]
+    pub print_job_id: Option<String>
+}
+
+impl Response {
+    /// Decode the status bitmask.
+    pub fn printer_status(&self) -> PrinterStatus {
+        self.status.into()
+    }
+
+    /// Decode the battery status. Returns `None` for printers without a battery.
+    pub fn battery_status(&self) -> Option<BatteryStatus> {
+        BatteryStatus::from_code(self.battery)
+    }
+}
+
+#[cfg(test)]
+impl Response {
+    /// A failed response with the given code.
+    pub(crate) fn failure(code: EposCode) -> Response {
+        Response { success: false, code, ..Default::default() }
+    }
+}
+
+/// The result of a successful print.
+#[derive(Debug)]
+pub struct PrintReceipt {
+    /// The print job ID of the document, if one was set. Pass it to `Builder::job_status` to check on the job later.
+    pub job_id: Option<String>,
+    /// The printer's response
+    pub response: Response
+}
+
+/// The state of a print job, as returned by `Builder::job_status`.
+#[derive(Debug)]
+pub enum JobStatus {
+    /// The job was printed
+    Printed(Response),
+    /// The job has been accepted, and is still printing
+    Printing(Response),
+    /// The printer has no record of the job, so it was never received, or it was too long ago
+    NotFound(Response),
+    /// The job failed, see the response code for why
+    Failed(Response)
+}
+
+impl From<Response> for JobStatus {
+    fn from(resp: Response) -> Self {
+        match resp.code {
+            _ if resp.success => JobStatus::Printed(resp),
+            EposCode::Printing => JobStatus::Printing(resp),
+            EposCode::JobNotFound => JobStatus::NotFound(resp),
+            _ => JobStatus::Failed(resp)
+        }
+    }
 }
 
 impl std::fmt::Display for Response {
@@ -128,33 +552,179 @@ impl std::fmt::Display for Response {
     }
 }
 
+/// Something that changed between two status reports, as reported by `Builder::watch_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusEvent {
+    /// The printer could not be reached
+    Unreachable,
+    /// The printer answered again after being unreachable
+    Reachable,
+    /// The printer went offline, or stopped responding to the ePOS service
+    Offline,
+    /// The printer came back online
+    Online,
+    /// The cover was opened
+    CoverOpened,
+    /// The cover was closed
+    CoverClosed,
+    /// The roll paper is running low
+    PaperNearEnd,
+    /// The roll paper ran out
+    PaperOut,
+    /// The roll paper was replaced, so it's no longer near its end
+    PaperLoaded,
+    /// Errors were raised, with the error bits that were newly set
+    Error(PrinterStatus),
+    /// The printer is ready again, after being unreachable or needing attention
+    Recovered
+}
+
+impl StatusEvent {
+    /// Bits that mean the printer can't print.
+    const OFFLINE: PrinterStatus = PrinterStatus::OFFLINE.union(PrinterStatus::NO_RESPONSE);
+    /// Bits that report a printer error.
+    const ERRORS: PrinterStatus = PrinterStatus::MECHANICAL_ERROR.union(PrinterStatus::AUTOCUTTER_ERROR)
+        .union(PrinterStatus::UNRECOVERABLE_ERROR).union(PrinterStatus::AUTO_RECOVERABLE_ERROR);
+
+    /// The events between two statuses, where `None` means the printer could not be reached.
+    /// Once the printer can be reached again, every condition it reports is treated as new.
+    pub fn diff(previous: Option<PrinterStatus>, current: Option<PrinterStatus>) -> Vec<StatusEvent> {
+        let mut events = Vec::new();
+        let Some(current) = current else {
+            if previous.is_some() {
+                events.push(StatusEvent::Unreachable);
+            }
+            return events;
+        };
+        if previous.is_none() {
+            events.push(StatusEvent::Reachable);
+        }
+        let old = previous.unwrap_or_default();
+
+        match (old.intersects(StatusEvent::OFFLINE), current.intersects(StatusEvent::OFFLINE)) {
+            (false, true) => events.push(StatusEvent::Offline),
+            (true, false) => events.push(StatusEvent::Online),
+            _ => {}
+        }
+        match (old.contains(PrinterStatus::COVER_OPEN), current.contains(PrinterStatus::COVER_OPEN)) {
+            (false, true) => events.push(StatusEvent::CoverOpened),
+            (true, false) => events.push(StatusEvent::CoverClosed),
+            _ => {}
+        }
+        if current.paper_end() && !old.paper_end() {
+            events.push(StatusEvent::PaperOut);
+        } else if current.paper_near_end() && !current.paper_end() && !old.paper_near_end() && !old.paper_end() {
+            events.push(StatusEvent::PaperNearEnd);
+        } else if (old.paper_end() || old.paper_near_end()) && !current.paper_end() && !current.paper_near_end() {
+            events.push(StatusEvent::PaperLoaded);
+        }
+        let errors = current.intersection(StatusEvent::ERRORS).difference(old);
+        if !errors.is_empty() {
+            events.push(StatusEvent::Error(errors));
+        }
+        if current.severity() == Severity::Ok && (previous.is_none() || old.severity() > Severity::Ok) {
+            events.push(StatusEvent::Recovered);
+        }
+        events
+    }
+}
+
+/// A change in the printer's status, as reported by `Builder::watch_status`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusChange {
+    /// The printer's status, or `None` if it could not be reached
+    pub status: Option<PrinterStatus>,
+    /// What changed since the last report. May be empty, such as when only `PAPER_FEED` changed
+    pub events: Vec<StatusEvent>
+}
+
 
 #[cfg(test)]
 mod tests {
-    use super::{PrinterStatus, Response};
+    use crate::error::ErrorKind;
+
+    use super::{BatteryStatus, ChargeLevel, EposCode, PowerSource, PrinterStatus, Response, Severity, StatusEvent};
+
+    #[test]
+    fn test_code() {
+        let resp: Response = quick_xml::de::from_str(r#"<response success="false" code="EPTR_COVER_OPEN" status="0" battery="0" xmlns=""/>"#).unwrap();
+        assert_eq!(resp.code, EposCode::CoverOpen);
+        assert_eq!(resp.code.kind(), ErrorKind::Operator);
+
+        assert_eq!(EposCode::from(String::from("EX_TIMEOUT")).kind(), ErrorKind::Retryable);
+        assert_eq!(EposCode::from(String::from("SchemaError")).kind(), ErrorKind::Programming);
+        assert_eq!(EposCode::from(String::from("EX_NEW")), EposCode::Other(String::from("EX_NEW")));
+        assert_eq!(String::from(EposCode::ReceiptEmpty), "EPTR_REC_EMPTY");
+    }
 
     #[test]
     fn test_status_bitmask() {
         let example = 251658262;
         let status = Response{status: example, ..Default::default()};
         let res: PrinterStatus = status.status.into();
-        let codes = PrinterStatus {
-            success: true,
-            drawer_kick_out_connector: true,
-            battery_offline_status: true,
-            buzzer_on: true,
-            label_wait_removal: true,
-            ..Default::default()
-        };
-        println!("status: {}", res);
-        assert_eq!(codes, res);
+        assert!(res.contains(PrinterStatus::PRINT_SUCCESS | PrinterStatus::DRAWER_KICK | PrinterStatus::BUZZER));
+        assert!(res.contains(PrinterStatus::RESERVED_FIXED));
+        assert!(!res.intersects(PrinterStatus::OFFLINE | PrinterStatus::COVER_OPEN | PrinterStatus::SPOOLER_STOPPED));
+        assert_eq!(u32::from(res), example);
+
+        let mut flags = PrinterStatus::OFFLINE | PrinterStatus::COVER_OPEN;
+        flags.remove(PrinterStatus::COVER_OPEN);
+        flags.insert(PrinterStatus::RECEIPT_NEAR_END);
+        assert_eq!(flags.bits(), 0x00020008);
+        assert_eq!(flags - PrinterStatus::OFFLINE, PrinterStatus::RECEIPT_NEAR_END);
+        assert_eq!(PrinterStatus::BATTERY_OFFLINE, PrinterStatus::DRAWER_KICK);
+        assert_eq!(PrinterStatus::from_bits_retain(0x00100000).bits(), 0x00100000);
+        assert!(PrinterStatus::all().contains(PrinterStatus::NO_LABEL));
+    }
+
+    #[test]
+    fn test_status_severity() {
+        assert_eq!(PrinterStatus::from(0x00000012).severity(), Severity::Ok);
+        assert_eq!(PrinterStatus::RECEIPT_NEAR_END_FIRST.severity(), Severity::Warning);
+        assert_eq!((PrinterStatus::RECEIPT_NEAR_END | PrinterStatus::COVER_OPEN).severity(), Severity::Error);
+        assert_eq!((PrinterStatus::UNRECOVERABLE_ERROR | PrinterStatus::OFFLINE).severity(), Severity::Fatal);
+    }
+
+    #[test]
+    fn test_status_events() {
+        let ready = PrinterStatus::from(0x00000012);
+        assert_eq!(StatusEvent::diff(Some(ready), Some(ready)), []);
+        assert_eq!(StatusEvent::diff(Some(ready), Some(ready | PrinterStatus::RECEIPT_NEAR_END)), [StatusEvent::PaperNearEnd]);
+        assert_eq!(StatusEvent::diff(Some(ready | PrinterStatus::RECEIPT_NEAR_END), Some(ready | PrinterStatus::RECEIPT_NEAR_END | PrinterStatus::RECEIPT_END | PrinterStatus::OFFLINE)),
+            [StatusEvent::Offline, StatusEvent::PaperOut]);
+        assert_eq!(StatusEvent::diff(Some(ready | PrinterStatus::RECEIPT_END | PrinterStatus::OFFLINE), Some(ready)),
+            [StatusEvent::Online, StatusEvent::PaperLoaded, StatusEvent::Recovered]);
+        assert_eq!(StatusEvent::diff(Some(ready), Some(ready | PrinterStatus::COVER_OPEN | PrinterStatus::AUTOCUTTER_ERROR)),
+            [StatusEvent::CoverOpened, StatusEvent::Error(PrinterStatus::AUTOCUTTER_ERROR)]);
+        assert_eq!(StatusEvent::diff(Some(ready | PrinterStatus::COVER_OPEN), Some(ready)), [StatusEvent::CoverClosed, StatusEvent::Recovered]);
+
+        assert_eq!(StatusEvent::diff(Some(ready), None), [StatusEvent::Unreachable]);
+        assert_eq!(StatusEvent::diff(None, None), []);
+        assert_eq!(StatusEvent::diff(None, Some(ready)), [StatusEvent::Reachable, StatusEvent::Recovered]);
+        assert_eq!(StatusEvent::diff(None, Some(ready | PrinterStatus::COVER_OPEN)), [StatusEvent::Reachable, StatusEvent::CoverOpened]);
     }
 
     #[test]
     fn test_status_to_string(){
-        let example = 251658262;
-        let status = Response{status: example, ..Default::default()};
-        let out: PrinterStatus = status.status.into();
-        assert_eq!(r#"{"success":true,"drawer_kick_out_connector":true,"battery_offline_status":true,"buzzer_on":true,"label_wait_removal":true}"#, out.to_string())
+        let out: PrinterStatus = 0x00000012.into();
+        assert_eq!("ok: print succeeded", out.to_string());
+
+        let out: PrinterStatus = 0x000A0028.into();
+        assert_eq!("error: offline, cover open, paper near end, paper out", out.to_string());
+        assert_eq!("PrinterStatus(0x000a0028: OFFLINE | COVER_OPEN | RECEIPT_NEAR_END | RECEIPT_END)", format!("{:?}", out));
+    }
+
+    #[test]
+    fn test_battery_status() {
+        let status = Response{battery: 0x3136, ..Default::default()};
+        assert_eq!(status.battery_status(), Some(BatteryStatus{source: PowerSource::Battery, level: ChargeLevel::Full}));
+        assert!(!status.battery_status().unwrap().should_take_out_of_service());
+
+        let low = BatteryStatus::from_code(0x3032).unwrap();
+        assert_eq!(low, BatteryStatus{source: PowerSource::Ac, level: ChargeLevel::Low});
+        assert!(low.should_take_out_of_service());
+
+        assert_eq!(BatteryStatus::from_code(0), None);
+        assert_eq!(BatteryStatus::from_code(0x3137), None);
     }
-}
\ No newline at end of file
+}
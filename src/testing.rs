@@ -0,0 +1,304 @@
+//! An in-process mock of the ePOS web service, for testing without a printer.
+//!
+//! ```rust
+//! # tokio_test::block_on(async {
+//! use epos_rs::Builder;
+//! use epos_rs::testing::{MockPrinter, MockResponse};
+//! use epos_rs::universal::Text;
+//!
+//! let printer = MockPrinter::start().await.unwrap();
+//! printer.push_response(MockResponse::error("EPTR_COVER_OPEN"));
+//!
+//! let mut handler = Builder::new(10000, "local_printer", printer.url()).unwrap().normal();
+//! handler.add(Text{text: String::from("hello\n"), ..Default::default()}).unwrap();
+//! assert!(handler.print().await.is_err());
+//! assert_eq!(printer.requests()[0].body, "<text>hello\n</text>");
+//! # })
+//! ```
+
+use std::{collections::{HashMap, VecDeque}, net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
+
+use log::debug;
+use quick_xml::{events::Event, Reader};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}, task::JoinHandle};
+
+use crate::soap::{header_job_id, ENDPOINT};
+
+/// A scripted reply from the mock printer.
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    /// Value of the `success` attribute
+    pub success: bool,
+    /// Value of the `code` attribute
+    pub code: String,
+    /// Value of the `status` bitmask
+    pub status: u32,
+    /// Value of the `battery` attribute
+    pub battery: u32,
+    /// Time to wait before replying
+    pub delay: Option<Duration>,
+    /// HTTP status code of the reply
+    pub http_status: u16,
+    /// Reply with this body instead of a SOAP envelope
+    pub raw_body: Option<String>
+}
+
+impl Default for MockResponse {
+    fn default() -> Self {
+        MockResponse::success()
+    }
+}
+
+impl MockResponse {
+    /// A successful reply from an idle, online printer.
+    pub fn success() -> MockResponse {
+        MockResponse { success: true, code: String::new(), status: 0x00000012, battery: 0, delay: None, http_status: 200, raw_body: None }
+    }
+
+    /// A failed reply with the given response code, such as `"EPTR_COVER_OPEN"`.
+    pub fn error(code: &str) -> MockResponse {
+        MockResponse { success: false, code: code.to_string(), ..MockResponse::success() }
+    }
+
+    /// A reply that isn't a SOAP envelope, such as the error page of a web server that is still starting.
+    pub fn http(http_status: u16, body: &str) -> MockResponse {
+        MockResponse { http_status, raw_body: Some(body.to_string()), ..MockResponse::success() }
+    }
+
+    /// Set the status bitmask of the reply.
+    pub fn with_status(self, status: u32) -> MockResponse {
+        MockResponse { status, ..self }
+    }
+
+    /// Set the battery status of the reply.
+    pub fn with_battery(self, battery: u32) -> MockResponse {
+        MockResponse { battery, ..self }
+    }
+
+    /// Wait before replying.
+    pub fn with_delay(self, delay: Duration) -> MockResponse {
+        MockResponse { delay: Some(delay), ..self }
+    }
+
+    /// Like the ePOS web service, the print job ID of the request is sent back.
+    fn to_xml(&self, job_id: Option<&str>) -> String {
+        let job_id = job_id.map(|id| format!(r#" printjobid="{}""#, quick_xml::escape::escape(id))).unwrap_or_default();
+        format!(concat!(r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body>"#,
+            r#"<response success="{}" code="{}" status="{}" battery="{}"{} xmlns="http://www.epson-pos.com/schemas/2011/03/epos-print"/>"#,
+            r#"</s:Body></s:Envelope>"#),
+            self.success, quick_xml::escape::escape(&self.code), self.status, self.battery, job_id)
+    }
+}
+
+/// A request received by the mock printer.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    /// The `devid` query parameter
+    pub dev_id: String,
+    /// The `timeout` query parameter
+    pub timeout: String,
+    /// All query parameters
+    pub params: HashMap<String, String>,
+    /// The `printjobid` sent in the SOAP header
+    pub print_job_id: Option<String>,
+    /// The complete SOAP envelope
+    pub envelope: String,
+    /// The contents of the `epos-print` element
+    pub body: String
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<RecordedRequest>,
+    script: VecDeque<MockResponse>,
+    fallback: MockResponse
+}
+
+/// A mock ePOS printer listening on a local port.
+/// Requests to `soap::ENDPOINT` are recorded and answered with scripted responses, in order.
+/// Once the script runs out, the default response is used. The server stops when the `MockPrinter` is dropped.
+pub struct MockPrinter {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    handle: JoinHandle<()>
+}
+
+impl Drop for MockPrinter {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl MockPrinter {
+    /// Start a mock printer on a random local port.
+    pub async fn start() -> std::io::Result<MockPrinter> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let server_state = state.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let conn_state = server_state.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_conn(stream, conn_state).await {
+                        debug!("mock printer connection failed: {}", err);
+                    }
+                });
+            }
+        });
+
+        Ok(MockPrinter { addr, state, handle })
+    }
+
+    /// The base URL of the printer, to be passed to `Builder::new`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The address the printer is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Queue a response for the next request.
+    pub fn push_response(&self, response: MockResponse) {
+        self.state.lock().unwrap().script.push_back(response);
+    }
+
+    /// Set the response used once all queued responses have been sent.
+    pub fn set_default_response(&self, response: MockResponse) {
+        self.state.lock().unwrap().fallback = response;
+    }
+
+    /// Return all requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+async fn handle_conn(mut stream: TcpStream, state: Arc<Mutex<State>>) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default().to_string();
+    let content_length: usize = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..read]);
+    }
+    let envelope = String::from_utf8_lossy(&buf[header_end..]).to_string();
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    if method != "POST" || path != ENDPOINT {
+        return stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+    }
+
+    let params: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let request = RecordedRequest {
+        dev_id: params.get("devid").cloned().unwrap_or_default(),
+        timeout: params.get("timeout").cloned().unwrap_or_default(),
+        body: epos_print_body(&envelope).unwrap_or_default(),
+        print_job_id: header_job_id(&envelope),
+        params,
+        envelope
+    };
+
+    let job_id = request.print_job_id.clone();
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request);
+        state.script.pop_front().unwrap_or_else(|| state.fallback.clone())
+    };
+    if let Some(delay) = response.delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let (content_type, body) = match response.raw_body {
+        Some(ref body) => ("text/html", body.clone()),
+        None => ("text/xml; charset=utf-8", response.to_xml(job_id.as_deref()))
+    };
+    let reply = format!("HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.http_status, content_type, body.len(), body);
+    stream.write_all(reply.as_bytes()).await
+}
+
+/// Return the contents of the `epos-print` element of an envelope.
+fn epos_print_body(envelope: &str) -> Option<String> {
+    let mut reader = Reader::from_str(envelope);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) if start.local_name().as_ref() == b"epos-print" => {
+                let span = reader.read_to_end(start.name()).ok()?;
+                return Some(envelope[span].to_string());
+            },
+            Event::Empty(start) if start.local_name().as_ref() == b"epos-print" => return Some(String::new()),
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{error::EPOSError, status::EposCode, Builder};
+
+    use super::{MockPrinter, MockResponse};
+
+    #[tokio::test]
+    async fn test_scripted_responses() {
+        let printer = MockPrinter::start().await.unwrap();
+        printer.push_response(MockResponse::error("EPTR_COVER_OPEN").with_status(0x00000028));
+        printer.push_response(MockResponse::success().with_delay(Duration::from_millis(100)));
+        let handler = Builder::new(10000, "local_printer", printer.url()).unwrap();
+
+        let resp = handler.status().await.unwrap();
+        assert!(!resp.success);
+        assert_eq!(resp.code, EposCode::CoverOpen);
+        assert_eq!(resp.status, 0x00000028);
+
+        let start = Instant::now();
+        let resp = handler.status().await.unwrap();
+        assert!(resp.success);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        let err = handler.reset().await;
+        assert!(err.is_ok());
+        printer.set_default_response(MockResponse::error("EX_TIMEOUT"));
+        let err = handler.reset().await.unwrap_err();
+        assert!(matches!(err, EPOSError::ResponseError { status } if status.code == EposCode::Timeout));
+
+        let requests = printer.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].dev_id, "local_printer");
+        assert_eq!(requests[0].timeout, "10000");
+        assert_eq!(requests[0].body, "");
+        assert_eq!(requests[3].body, "<reset/>");
+    }
+}
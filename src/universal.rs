@@ -1,11 +1,12 @@
 //! Types that are available in normal mode and page mode.
+use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::{Deserialize, Serialize};
 
-use crate::{formatters::{Font, Lang, Align, FeedPos}, page::PageItem, barcodes::{BarcodeType, HRI, SymbolType, ErrorCorrectionLevel}, normal::NormalItem};
+use crate::{raster::{self, ImageOptions}, error::ValidationError, formatters::{Font, Lang, Align, FeedPos, SoundPattern, CodePage, PaperType}, page::PageItem, barcodes::{maxicode_postal_code, BarcodeType, HRI, SymbolType, ErrorCorrectionLevel}, normal::NormalItem};
 
 /// Produces a text line.
 /// Warning: If you're just printing a text value, the printer may fail to print unless you include a \n newline.
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename = "text")]
 pub struct Text {
     #[serde(rename = "$text")]
@@ -48,7 +49,7 @@ impl PageItem for Text {}
 impl NormalItem for Text{}
 
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename ="feed")]
 /// Feed paper. At least one of the options for setting the length to feed must be set.
 /// Keep in mind that in page mode
@@ -70,7 +71,45 @@ pub struct Feed {
 impl PageItem for Feed{}
 impl NormalItem for Feed{}
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+/// Configure the paper layout for label or black mark paper.
+/// A layout isn't added like other items; set it with `set_layout()` on a builder, and it will be sent ahead of the document body.
+/// All lengths are in units of 0.1 mm.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename ="layout")]
+pub struct Layout {
+    #[serde(rename = "@type")]
+    /// Specifies the paper type
+    pub paper_type: PaperType,
+    #[serde(rename = "@width", skip_serializing_if = "Option::is_none")]
+    /// Paper width
+    pub width: Option<u16>,
+    #[serde(rename = "@height", skip_serializing_if = "Option::is_none")]
+    /// Distance from the print reference mark to the next print reference mark (label or black mark)
+    pub height: Option<u16>,
+    #[serde(rename = "@margin-top", skip_serializing_if = "Option::is_none")]
+    /// Distance from the print reference mark to the top of the print area
+    pub margin_top: Option<i16>,
+    #[serde(rename = "@margin-bottom", skip_serializing_if = "Option::is_none")]
+    /// Distance from the bottom of the print area to the next print reference mark
+    pub margin_bottom: Option<i16>,
+    #[serde(rename = "@offset-cut", skip_serializing_if = "Option::is_none")]
+    /// Distance from the print reference mark to the cutting position
+    pub offset_cut: Option<i16>,
+    #[serde(rename = "@offset-label", skip_serializing_if = "Option::is_none")]
+    /// Distance from the print reference mark to the top of the next label
+    pub offset_label: Option<i16>
+}
+
+impl Layout {
+    /// Returns true if the layout is for label paper, with or without black marks.
+    pub fn is_label(&self) -> bool {
+        matches!(self.paper_type, PaperType::Label | PaperType::LabelBm)
+    }
+}
+
+/// Print a 1D barcode.
+/// Create one with `Barcode::builder`, which checks the data against the rules for the barcode type.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename ="barcode")]
 pub struct Barcode {
     #[serde(rename = "$text")]
@@ -101,8 +140,120 @@ pub struct Barcode {
 impl PageItem for Barcode {}
 impl NormalItem for Barcode {}
 
+impl Barcode {
+    /// Start building a barcode of the given type. The data is validated when `BarcodeBuilder::build` is called.
+    ///
+    /// ```
+    /// use epos_rs::{barcodes::{BarcodeType, HRI}, universal::Barcode};
+    ///
+    /// let barcode = Barcode::builder(BarcodeType::EAN13, "4901234567894").hri(HRI::Below).width(3).build().unwrap();
+    /// assert_eq!(barcode.text(), "4901234567894");
+    /// assert!(Barcode::builder(BarcodeType::EAN13, "4901234567890").build().is_err());
+    /// ```
+    pub fn builder(barcode_type: BarcodeType, text: impl Into<String>) -> BarcodeBuilder {
+        BarcodeBuilder { barcode: Barcode { text: text.into(), barcode_type, ..Default::default() } }
+    }
+
+    /// Check the data against the rules for the barcode type, and the width against the 2-6 range.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self.width {
+            Some(width) if !(2..=6).contains(&width) => Err(ValidationError::OutOfRange { field: "barcode width", value: width.into(), min: 2, max: 6 }),
+            _ => self.barcode_type.validate(&self.text)
+        }
+    }
+
+    /// The data encoded in the barcode
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The barcode type
+    pub fn barcode_type(&self) -> &BarcodeType {
+        &self.barcode_type
+    }
+
+    /// Where the human readable interpretation is printed
+    pub fn hri(&self) -> Option<&HRI> {
+        self.hri.as_ref()
+    }
+
+    /// The font of the human readable interpretation
+    pub fn font(&self) -> Option<&Font> {
+        self.font.as_ref()
+    }
+
+    /// The module width in dots
+    pub fn width(&self) -> Option<u8> {
+        self.width
+    }
+
+    /// The height in dots
+    pub fn height(&self) -> Option<u8> {
+        self.height
+    }
+
+    /// The print position
+    pub fn align(&self) -> Option<&Align> {
+        self.align.as_ref()
+    }
+
+    pub(crate) fn set_text(&mut self, text: String) {
+        self.text = text;
+    }
+}
+
+/// Builder for a `Barcode`, created with `Barcode::builder`.
+#[derive(Debug, Clone)]
+pub struct BarcodeBuilder {
+    barcode: Barcode
+}
+
+impl BarcodeBuilder {
+    /// Set where the human readable interpretation is printed
+    pub fn hri(mut self, hri: HRI) -> Self {
+        self.barcode.hri = Some(hri);
+        self
+    }
+
+    /// Set the font of the human readable interpretation
+    pub fn font(mut self, font: Font) -> Self {
+        self.barcode.font = Some(font);
+        self
+    }
+
+    /// Set the module width in dots. Must be a value from 2-6
+    pub fn width(mut self, width: u8) -> Self {
+        self.barcode.width = Some(width);
+        self
+    }
+
+    /// Set the height in dots
+    pub fn height(mut self, height: u8) -> Self {
+        self.barcode.height = Some(height);
+        self
+    }
+
+    /// Set the print position
+    pub fn align(mut self, align: Align) -> Self {
+        self.barcode.align = Some(align);
+        self
+    }
+
+    /// Rotate the barcode
+    pub fn rotate(mut self, rotate: bool) -> Self {
+        self.barcode.rotate = Some(rotate);
+        self
+    }
+
+    /// Validate and return the barcode.
+    pub fn build(self) -> Result<Barcode, ValidationError> {
+        self.barcode.validate()?;
+        Ok(self.barcode)
+    }
+}
+
 /// Print a 2D barcode
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename ="symbol")]
 pub struct Symbol {
     #[serde(rename = "$text")]
@@ -142,8 +293,94 @@ pub struct Symbol {
 impl PageItem for Symbol {}
 impl NormalItem for Symbol {}
 
-/// Print a bitmap raster image
-#[derive(Deserialize, Serialize, Debug, Default)]
+impl Symbol {
+    /// Check the parameters against the ranges supported by the symbol type.
+    /// Returns an error naming the first field the printer would reject.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let symbol = self.symbol_type.name();
+        let (width, level) = match self.symbol_type {
+            SymbolType::PDF417 | SymbolType::PDF415Trunc => ((2, 8), LevelKind::Pdf417),
+            SymbolType::QRCode1 | SymbolType::QRCode2 => ((1, 16), LevelKind::Qr),
+            SymbolType::MaxiCodeMode2 | SymbolType::MaxiCodeMode3 | SymbolType::MaxiCodeMode4
+                | SymbolType::MaxiCodeMode5 | SymbolType::MaxiCodeMode6 => ((0, u8::MAX), LevelKind::None),
+            SymbolType::GS1DatabarStacked | SymbolType::GS1DatabarStackedOmnidirectional
+                | SymbolType::GS1DatabarExpandedStacked => ((2, 8), LevelKind::None),
+            SymbolType::AztecCodeFullRange | SymbolType::AztecCodeCompact => ((2, 16), LevelKind::Aztec),
+            SymbolType::DatamatrixSquare | SymbolType::DatamatrixRectangle8 | SymbolType::DatamatrixRectangle12
+                | SymbolType::DatamatrixRectangle16 => ((2, 16), LevelKind::None),
+        };
+
+        if let Some(value) = self.width {
+            if !(width.0..=width.1).contains(&value) {
+                return Err(ValidationError::OutOfRange { field: "symbol width", value: value.into(), min: width.0.into(), max: width.1.into() });
+            }
+        }
+
+        match (level, self.level) {
+            (_, None | Some(ErrorCorrectionLevel::Default)) => {},
+            (LevelKind::Pdf417, Some(value)) if value.is_pdf417() => {},
+            (LevelKind::Qr, Some(value)) if value.is_qr() => {},
+            (LevelKind::Aztec, Some(ErrorCorrectionLevel::Int(value))) => {
+                if !(5..=95).contains(&value) {
+                    return Err(ValidationError::OutOfRange { field: "symbol level", value: value.into(), min: 5, max: 95 });
+                }
+            },
+            (_, Some(_)) => return Err(ValidationError::InvalidParameter { symbol, field: "level", reason: level.reason() }),
+        }
+
+        if matches!(self.symbol_type, SymbolType::PDF417 | SymbolType::PDF415Trunc) {
+            if let Some(height) = self.height {
+                if !(2..=8).contains(&height) {
+                    return Err(ValidationError::OutOfRange { field: "symbol height", value: height.into(), min: 2, max: 8 });
+                }
+            }
+            if let Some(size) = self.size {
+                if size > 30 {
+                    return Err(ValidationError::OutOfRange { field: "symbol size", value: size.into(), min: 0, max: 30 });
+                }
+            }
+        }
+
+        if matches!(self.symbol_type, SymbolType::GS1DatabarExpandedStacked) {
+            if let Some(size) = self.size.filter(|size| *size != 0 && *size < 106) {
+                return Err(ValidationError::OutOfRange { field: "symbol size", value: size.into(), min: 106, max: u8::MAX.into() });
+            }
+        }
+
+        if matches!(self.symbol_type, SymbolType::MaxiCodeMode2) {
+            let postal_code = maxicode_postal_code(&self.text);
+            if !(1..=9).contains(&postal_code.len()) || !postal_code.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ValidationError::InvalidParameter { symbol, field: "text", reason: "MaxiCode mode 2 needs a numeric postal code of 1 to 9 digits" });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The family of error correction levels a symbol type accepts.
+#[derive(Clone, Copy)]
+enum LevelKind {
+    Pdf417,
+    Qr,
+    Aztec,
+    None
+}
+
+impl LevelKind {
+    fn reason(&self) -> &'static str {
+        match self {
+            LevelKind::Pdf417 => "PDF417 only accepts level_0 to level_8",
+            LevelKind::Qr => "QR Code only accepts level_l to level_h",
+            LevelKind::Aztec => "Aztec Code only accepts a level from 5 to 95",
+            LevelKind::None => "this symbol type only accepts the default level"
+        }
+    }
+}
+
+/// Print a bitmap raster image.
+/// Create one from a pixel buffer with `Image::from_luma` or `Image::from_rgba`, or from a file with `Image::open` (requires the `image` feature).
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename ="image")]
 pub struct Image {
     #[serde(rename = "$text")]
@@ -155,15 +392,234 @@ pub struct Image {
     height: i32
 }
 
+impl Image {
+    /// Create an image from a packed 1-bit raster: one bit per dot, most significant bit first, `1` for a printed dot,
+    /// with each row padded to a whole byte.
+    pub fn from_raster(raster: &[u8], width: u32, height: u32) -> Result<Image, ValidationError> {
+        check_dimensions(width, height)?;
+        let expected = width.div_ceil(8) as usize * height as usize;
+        if raster.len() != expected {
+            return Err(ValidationError::ImageBufferSize { expected, actual: raster.len() })
+        }
+        Ok(Image { text: STANDARD.encode(raster), width: width as i32, height: height as i32 })
+    }
+
+    /// Create an image from an 8-bit grayscale buffer, one byte per pixel, row by row.
+    pub fn from_luma(pixels: &[u8], width: u32, height: u32, options: &ImageOptions) -> Result<Image, ValidationError> {
+        check_dimensions(width, height)?;
+        let expected = width as usize * height as usize;
+        if pixels.len() != expected {
+            return Err(ValidationError::ImageBufferSize { expected, actual: pixels.len() })
+        }
+
+        let (pixels, width, height) = match options.width {
+            Some(0) => return Err(ValidationError::OutOfRange { field: "image width", value: 0, min: 1, max: i32::MAX.into() }),
+            Some(new_width) => {
+                let (scaled, new_height) = raster::scale(pixels, width, height, new_width);
+                (scaled, new_width, new_height)
+            },
+            None => (pixels.to_vec(), width, height)
+        };
+        let dots = raster::dither(&pixels, width, height, options.dither);
+        Image::from_raster(&raster::pack(&dots, width, height), width, height)
+    }
+
+    /// Create an image from an 8-bit RGBA buffer, four bytes per pixel, row by row.
+    /// Transparent pixels are printed as a white background.
+    pub fn from_rgba(pixels: &[u8], width: u32, height: u32, options: &ImageOptions) -> Result<Image, ValidationError> {
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(ValidationError::ImageBufferSize { expected, actual: pixels.len() })
+        }
+        Image::from_luma(&raster::rgba_to_luma(pixels), width, height, options)
+    }
+
+    /// Load a PNG or JPEG image from a file.
+    #[cfg(feature = "image")]
+    pub fn open<P: AsRef<std::path::Path>>(path: P, options: &ImageOptions) -> Result<Image, crate::error::EPOSError> {
+        let img = image::open(path)?;
+        Ok(Image::from_dynamic_image(&img, options)?)
+    }
+
+    /// Create an image from an image decoded by the `image` crate.
+    #[cfg(feature = "image")]
+    pub fn from_dynamic_image(img: &image::DynamicImage, options: &ImageOptions) -> Result<Image, ValidationError> {
+        let rgba = img.to_rgba8();
+        Image::from_rgba(rgba.as_raw(), rgba.width(), rgba.height(), options)
+    }
+
+    /// Image width in dots.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Image height in dots.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Decode the packed 1-bit raster, in the layout taken by `from_raster`.
+    /// Returns `None` if the image data is not valid base64.
+    pub fn to_raster(&self) -> Option<Vec<u8>> {
+        STANDARD.decode(&self.text).ok()
+    }
+}
+
+fn check_dimensions(width: u32, height: u32) -> Result<(), ValidationError> {
+    if width == 0 || width > i32::MAX as u32 {
+        return Err(ValidationError::OutOfRange { field: "image width", value: width.into(), min: 1, max: i32::MAX.into() })
+    }
+    if height == 0 || height > i32::MAX as u32 {
+        return Err(ValidationError::OutOfRange { field: "image height", value: height.into(), min: 1, max: i32::MAX.into() })
+    }
+    Ok(())
+}
+
 impl PageItem for Image {}
 impl NormalItem for Image {}
 
+/// Sound the buzzer, on printers that have one.
+/// As with any other command, a failure is reported through the `status::Response` returned by `print()`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename ="sound")]
+pub struct Sound {
+    #[serde(rename = "@pattern", skip_serializing_if = "Option::is_none")]
+    /// Specifies the buzzer pattern. Defaults to `SoundPattern::PatternA`.
+    pub pattern: Option<SoundPattern>,
+    #[serde(rename = "@repeat", skip_serializing_if = "Option::is_none")]
+    /// Number of times the pattern is repeated. 0 repeats until the printer is reset.
+    pub repeat: Option<u8>,
+    #[serde(rename = "@cycle", skip_serializing_if = "Option::is_none")]
+    /// Time of one repeat cycle, in milliseconds. Must be a value between 1000-60000
+    pub cycle: Option<u16>
+}
+
+impl Sound {
+    /// Check the cycle against the 1000-60000 range.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self.cycle {
+            Some(cycle) if !(1000..=60000).contains(&cycle) => Err(ValidationError::OutOfRange { field: "sound cycle", value: cycle.into(), min: 1000, max: 60000 }),
+            _ => Ok(())
+        }
+    }
+}
+
+impl PageItem for Sound {}
+impl NormalItem for Sound {}
+
+/// Send raw ESC/POS command bytes to the printer, for features that aren't covered by the other types.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename ="command")]
+pub struct Command {
+    #[serde(rename = "$text")]
+    /// hex-encoded command bytes
+    text: String
+}
+
+impl Command {
+    /// Create a command from raw ESC/POS bytes.
+    pub fn new(bytes: &[u8]) -> Command {
+        let text = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        Command { text }
+    }
+
+    /// Select a character code table (`ESC t n`).
+    pub fn code_page(page: CodePage) -> Command {
+        Command::new(&[0x1b, 0x74, page.number()])
+    }
+
+    /// Set the print density (`GS ( K`), relative to the printer's standard density.
+    /// `level` must be a value between -6 (lightest) and 6 (darkest), 0 is the standard density.
+    pub fn print_density(level: i8) -> Result<Command, ValidationError> {
+        if !(-6..=6).contains(&level) {
+            return Err(ValidationError::OutOfRange { field: "print density", value: level.into(), min: -6, max: 6 })
+        }
+        Ok(Command::new(&[0x1d, 0x28, 0x4b, 0x02, 0x00, 0x31, level as u8]))
+    }
+
+    /// Decode the command bytes. Returns `None` if the command is not valid hex.
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        let text = self.text.trim();
+        if !text.len().is_multiple_of(2) {
+            return None;
+        }
+        (0..text.len()).step_by(2).map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok()).collect()
+    }
+}
+
+impl PageItem for Command {}
+impl NormalItem for Command {}
+
+
+/// An element kept exactly as it was written, such as an unsupported element read by `parse::parse` with `UnknownElements::Keep`.
+/// It is sent to the printer unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raw {
+    name: String,
+    xml: String
+}
+
+impl Raw {
+    pub(crate) fn new(name: &str, xml: &str) -> Raw {
+        Raw { name: name.to_string(), xml: xml.to_string() }
+    }
+
+    /// The element name, without any namespace prefix
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The complete element
+    pub fn xml(&self) -> &str {
+        &self.xml
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::formatters::Font;
+    use crate::{barcodes::{BarcodeType, ErrorCorrectionLevel, SymbolType, HRI}, error::ValidationError, formatters::{CodePage, Font, PaperType, SoundPattern}};
 
-    use super::Text;
+    use crate::raster::{Dither, ImageOptions};
+
+    use super::{Barcode, Command, Image, Layout, Sound, Symbol, Text};
+
+    #[test]
+    fn test_symbol() {
+        let qr = Symbol{text: "hello".to_string(), symbol_type: SymbolType::QRCode2, level: Some(ErrorCorrectionLevel::LevelM), width: Some(16), ..Default::default()};
+        assert!(qr.validate().is_ok());
+        let err = Symbol{width: Some(17), ..qr.clone()}.validate().unwrap_err();
+        assert_eq!(err, ValidationError::OutOfRange { field: "symbol width", value: 17, min: 1, max: 16 });
+
+        let pdf = Symbol{symbol_type: SymbolType::PDF417, width: Some(4), ..qr.clone()};
+        assert!(matches!(pdf.validate(), Err(ValidationError::InvalidParameter { symbol: "pdf417_standard", field: "level", .. })));
+        assert!(Symbol{level: Some(ErrorCorrectionLevel::Level3), width: Some(8), ..pdf}.validate().is_ok());
+
+        let aztec = Symbol{text: "hello".to_string(), symbol_type: SymbolType::AztecCodeCompact, level: Some(ErrorCorrectionLevel::Int(23)), ..Default::default()};
+        assert_eq!(quick_xml::se::to_string(&aztec).unwrap(), r#"<symbol type="azteccode_compact" level="23">hello</symbol>"#);
+        assert!(aztec.validate().is_ok());
+        let err = Symbol{level: Some(ErrorCorrectionLevel::Int(96)), ..aztec}.validate().unwrap_err();
+        assert_eq!(err, ValidationError::OutOfRange { field: "symbol level", value: 96, min: 5, max: 95 });
+
+        let stacked = Symbol{text: "(01)04912345123459".to_string(), symbol_type: SymbolType::GS1DatabarExpandedStacked, size: Some(105), ..Default::default()};
+        assert!(matches!(stacked.validate(), Err(ValidationError::OutOfRange { field: "symbol size", .. })));
+
+        let maxi = Symbol{text: "908063840\\x1d850\\x1d001\\x1d\\x04".to_string(), symbol_type: SymbolType::MaxiCodeMode2, ..Default::default()};
+        assert!(maxi.validate().is_ok());
+        assert!(Symbol{text: "[)>\x1e01\x1d96123456789\x1d850".to_string(), ..maxi.clone()}.validate().is_ok());
+        let err = Symbol{text: "AB12\x1d850\x1d001".to_string(), ..maxi}.validate().unwrap_err();
+        assert!(matches!(err, ValidationError::InvalidParameter { symbol: "maxicode_mode_2", field: "text", .. }));
+    }
+
+    #[test]
+    fn test_barcode() {
+        let barcode = Barcode::builder(BarcodeType::Code39, "ABC-123").hri(HRI::Below).width(2).height(48).build().unwrap();
+        assert_eq!(quick_xml::se::to_string(&barcode).unwrap(),
+            r#"<barcode type="code39" hri="below" width="2" height="48">ABC-123</barcode>"#);
+
+        let err = Barcode::builder(BarcodeType::Code39, "ABC").width(7).build().unwrap_err();
+        assert_eq!(err, ValidationError::OutOfRange { field: "barcode width", value: 7, min: 2, max: 6 });
+        assert!(Barcode::builder(BarcodeType::Code39, "abc").build().is_err());
+    }
 
     #[test]
     fn test_text() {
@@ -183,4 +639,71 @@ mod tests {
         let out = quick_xml::se::to_string(&test).unwrap();
         assert_eq!(out, String::from(r#"<text font="font_a" smoothing="true" dw="true" dh="true" width="20" height="20" ul="true" em="true" color="true" lang="en" align="center">test</text>"#))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sound() {
+        let test = Sound{pattern: Some(SoundPattern::Receipt), repeat: Some(2), cycle: Some(1000)};
+        let out = quick_xml::se::to_string(&test).unwrap();
+        assert_eq!(out, String::from(r#"<sound pattern="receipt" repeat="2" cycle="1000"/>"#));
+
+        assert!(test.validate().is_ok());
+        assert!(Sound::default().validate().is_ok());
+        let err = Sound{cycle: Some(999), ..test.clone()}.validate().unwrap_err();
+        assert_eq!(err, ValidationError::OutOfRange { field: "sound cycle", value: 999, min: 1000, max: 60000 });
+        assert!(Sound{cycle: Some(60001), ..test}.validate().is_err());
+    }
+
+    #[test]
+    fn test_command() {
+        let test = Command::new(&[0x1b, 0x40]);
+        let out = quick_xml::se::to_string(&test).unwrap();
+        assert_eq!(out, String::from(r#"<command>1b40</command>"#));
+
+        let out = quick_xml::se::to_string(&Command::code_page(CodePage::WPC1252)).unwrap();
+        assert_eq!(out, String::from(r#"<command>1b7410</command>"#));
+
+        let out = quick_xml::se::to_string(&Command::print_density(-1).unwrap()).unwrap();
+        assert_eq!(out, String::from(r#"<command>1d284b020031ff</command>"#));
+
+        let err = Command::print_density(7).unwrap_err();
+        assert_eq!(err, ValidationError::OutOfRange { field: "print density", value: 7, min: -6, max: 6 });
+    }
+
+    #[test]
+    fn test_layout() {
+        let test = Layout{paper_type: PaperType::Label, width: Some(580), height: Some(300), margin_top: Some(-10),
+            margin_bottom: Some(0), offset_cut: Some(20), offset_label: Some(0)};
+        let out = quick_xml::se::to_string(&test).unwrap();
+        assert_eq!(out, String::from(r#"<layout type="label" width="580" height="300" margin-top="-10" margin-bottom="0" offset-cut="20" offset-label="0"/>"#));
+    }
+
+    #[test]
+    fn test_image() {
+        // a 2x2 checkerboard, scaled up to 4x4
+        let options = ImageOptions{width: Some(4), dither: Dither::Threshold(128)};
+        let test = Image::from_luma(&[0, 255, 255, 0], 2, 2, &options).unwrap();
+        assert_eq!((test.width(), test.height()), (4, 4));
+        let out = quick_xml::se::to_string(&test).unwrap();
+        // rows are 0b1100_0000, 0b1100_0000, 0b0011_0000, 0b0011_0000
+        assert_eq!(out, String::from(r#"<image width="4" height="4">wMAwMA==</image>"#));
+
+        let rgba = Image::from_rgba(&[0, 0, 0, 255, 0, 0, 0, 0], 2, 1, &ImageOptions::default()).unwrap();
+        assert_eq!(quick_xml::se::to_string(&rgba).unwrap(), String::from(r#"<image width="2" height="1">gA==</image>"#));
+
+        let err = Image::from_luma(&[0, 0, 0], 2, 2, &options).unwrap_err();
+        assert_eq!(err, ValidationError::ImageBufferSize { expected: 4, actual: 3 });
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_image_file() {
+        let path = std::env::temp_dir().join("epos_rs_test_image.png");
+        image::GrayImage::from_fn(16, 8, |x, _| image::Luma([if x < 8 { 0 } else { 255 }])).save(&path).unwrap();
+
+        let options = ImageOptions{width: Some(8), dither: Dither::Atkinson};
+        let test = Image::open(&path, &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((test.width(), test.height()), (8, 4));
+        assert_eq!(quick_xml::se::to_string(&test).unwrap(), String::from(r#"<image width="8" height="4">8PDw8A==</image>"#));
+    }
+}